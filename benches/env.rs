@@ -0,0 +1,60 @@
+#![feature(test)]
+#![feature(box_syntax)]
+
+extern crate nf2llvmir;
+extern crate test;
+
+use nf2llvmir::{env::Env, ident::Ident, *};
+use test::Bencher;
+
+const DEPTH: usize = 2000;
+
+// let x0: Int = 0 in let x1: Int = (load x0) + 1 in ... load x{DEPTH - 1}
+fn let_chain(depth: usize) -> Nf {
+    let var = |i: usize| Expr::Var(Ident(format!("x{}", i)));
+    let mut body = Expr::Load(box var(depth - 1));
+    for i in (0..depth).rev() {
+        let init = if i == 0 {
+            Expr::Const(Literal::Int(0))
+        } else {
+            Expr::BinOp(
+                BinOp::Add,
+                box Expr::Load(box var(i - 1)),
+                box Expr::Const(Literal::Int(1)),
+            )
+        };
+        body = Expr::Let(Ident(format!("x{}", i)), Type::Int, box init, box body);
+    }
+    Nf {
+        funcs: vec![],
+        body: Some(body),
+    }
+}
+
+#[bench]
+fn env_add_lookup(b: &mut Bencher) {
+    let names: Vec<Ident> = (0..DEPTH).map(|i| Ident(format!("x{}", i))).collect();
+    b.iter(|| {
+        let mut env = Env::new();
+        for (i, name) in names.iter().enumerate() {
+            env = env.add(name.clone(), i);
+            test::black_box(env.lookup(&names[i / 2]));
+        }
+    });
+}
+
+#[bench]
+fn typecheck_let_chain(b: &mut Bencher) {
+    let nf = let_chain(DEPTH);
+    b.iter(|| typecheck::typing(&nf).unwrap());
+}
+
+#[bench]
+fn codegen_let_chain(b: &mut Bencher) {
+    let nf = typecheck::typing(&let_chain(DEPTH)).unwrap();
+    b.iter(|| {
+        let mut out = vec![];
+        codegen::gen(&mut out, &nf, "let_chain").unwrap();
+        out
+    });
+}
@@ -1,58 +1,185 @@
+mod abi;
 mod base;
 mod build;
+mod debug;
+mod handle;
 pub mod error;
 mod lit;
+mod pass;
+mod target;
 mod typ;
 mod util;
 
 #[cfg(test)]
 mod test;
 
-pub use self::base::*;
-pub use self::build::*;
-pub use self::lit::*;
-pub use self::typ::*;
-pub use self::util::*;
+pub use self::base::{Arithmetic, Base, Module, OutputKind};
+pub use self::handle::{Context, OwnedModule};
+pub use self::pass::OptLevel;
+pub use error::Error;
 
-use error::Error;
+// shared by the helper modules, which are not part of the API
+use self::base::{LBasicBlock, LBuilder, LContext, LModule, LType, LValue};
+use self::debug::DebugInfo;
+use self::handle::{Builder, Message};
+use self::target::{host_triple, TargetMachine};
+use self::util::append_block;
 
-use crate::{env::Env, *};
+use crate::{env::Env, ident::NameSupply, is_constant::Constant, session::Options, *};
 
-pub fn gen<T: std::io::Write>(out: &mut T, nf: &Nf, name: &str) -> Result<(), error::Error> {
-    let base = Base::new(name);
-    apply_nf(&base, nf)?;
-    util::validate_module(base.module)?;
-    write!(out, "{}", util::print_module(base.module)?)?;
+pub fn gen<T: std::io::Write>(
+    out: &mut T,
+    nf: &typed::Nf,
+    name: &str,
+) -> Result<(), error::Error> {
+    gen_with_options(out, nf, name, &Options::default())
+}
+
+pub fn gen_optimized<T: std::io::Write>(
+    out: &mut T,
+    nf: &typed::Nf,
+    name: &str,
+    opt_level: OptLevel,
+) -> Result<(), error::Error> {
+    let options = Options {
+        opt_level,
+        ..Options::default()
+    };
+    gen_with_options(out, nf, name, &options)
+}
+
+/// Generates `nf` without `main`, to be linked into programs written in other languages.
+pub fn gen_library<T: std::io::Write>(
+    out: &mut T,
+    nf: &typed::Nf,
+    name: &str,
+) -> Result<(), error::Error> {
+    let options = Options {
+        output: OutputKind::Library,
+        ..Options::default()
+    };
+    gen_with_options(out, nf, name, &options)
+}
+
+pub fn gen_with_options<T: std::io::Write>(
+    out: &mut T,
+    nf: &typed::Nf,
+    name: &str,
+    options: &Options,
+) -> Result<(), error::Error> {
+    let module = gen_module(nf, name, options)?;
+    write!(out, "{}", module.print()?)?;
     Ok(())
 }
 
-fn apply_nf(base: &Base, nf: &Nf) -> Result<(), Error> {
+/// Generates `nf` into a new module in its own context, which can be sent to another thread.
+pub fn gen_module(nf: &typed::Nf, name: &str, options: &Options) -> Result<Module, error::Error> {
+    gen_module_with_names(nf, name, options, &NameSupply::new())
+}
+
+/// Same as `gen_module`, but values and debug info keep the source names of identifiers
+/// renamed by `names`.
+pub fn gen_module_with_names(
+    nf: &typed::Nf,
+    name: &str,
+    options: &Options,
+    names: &NameSupply,
+) -> Result<Module, error::Error> {
+    Module::generate(|context| {
+        let mut base = Base::with_options(context, name, options)?;
+        base.source_names = names.origins().clone();
+        emit(&base, nf)?;
+        Ok(base.into_module())
+    })
+}
+
+/// Generates each unit into its own module configured by `options`, and links them into
+/// a single module named `name`.
+pub fn gen_units<T: std::io::Write>(
+    out: &mut T,
+    units: &[(&str, &typed::Nf)],
+    name: &str,
+    options: &Options,
+) -> Result<(), error::Error> {
+    let context = Context::new();
+    // the debug info of the units is linked in, and this module has none of its own
+    let link_options = Options {
+        debug_info: None,
+        ..options.clone()
+    };
+    let base = Base::with_options(&context, name, &link_options)?;
+    for (unit_name, nf) in units.iter() {
+        let unit = Base::with_options(&context, unit_name, options)?;
+        emit(&unit, nf)?;
+        base.link(unit)?;
+    }
+    util::validate_module(base.module())?;
+    write!(out, "{}", util::print_module(base.module())?)?;
+    Ok(())
+}
+
+/// Generates `nf` into the module of `base`, validates the result,
+/// and optimizes it at `base.opt_level`.
+pub fn emit(base: &Base, nf: &typed::Nf) -> Result<(), Error> {
+    apply_nf(base, nf)?;
+    if let Some(ref debug_info) = base.debug_info {
+        debug_info.finalize();
+    }
+    util::validate_module(base.module())?;
+    pass::optimize(base.module(), base.opt_level);
+    Ok(())
+}
+
+fn apply_nf(base: &Base, nf: &typed::Nf) -> Result<(), Error> {
     let mut env = Env::new();
 
+    for decl in nf.imports.iter() {
+        let typ = apply_type(&decl.typ, base)?;
+        let gen_func = base.declare_function(&decl.symbol, typ)?;
+        // as the public function of the unit which defines it
+        abi::add_extension_attributes(gen_func, base);
+        env = env.add(decl.name.clone(), gen_func);
+    }
+
     for func in nf.funcs.iter() {
-        let gen_func = add_function(base, func)?;
+        let gen_func = add_function(base, &base.symbol_name(&func.name), func)?;
+        util::set_linkage(
+            gen_func,
+            match func.visibility {
+                Visibility::Public if base.output == OutputKind::Library => {
+                    llvm::LLVMLinkage::LLVMExternalLinkage
+                }
+                Visibility::Public => base.linkage,
+                Visibility::Private => llvm::LLVMLinkage::LLVMInternalLinkage,
+            },
+        );
         env = env.add(func.name.clone(), gen_func);
     }
 
     for func in nf.funcs.iter() {
         let gen_func = env.lookup(&func.name).unwrap();
-        add_function_body(base, gen_func, func, &env)?;
+        add_function_body(base, gen_func, &base.symbol_name(&func.name), func, &env)?;
+    }
+
+    if base.output == OutputKind::Library {
+        return Ok(());
     }
 
     if let Some(ref body) = &nf.body {
-        let main_func = Func {
+        let main_func = typed::Func {
             name: Ident::new("main"),
             params: vec![],
             ret_type: Type::Int,
             body: body.clone(),
+            visibility: Visibility::Public,
         };
-        let gen_main_func = add_function(base, &main_func)?;
-        add_function_body(base, gen_main_func, &main_func, &env)?;
+        let gen_main_func = add_function(base, "main", &main_func)?;
+        add_function_body(base, gen_main_func, "main", &main_func, &env)?;
     }
     Ok(())
 }
 
-fn add_function(base: &Base, func: &Func) -> Result<LValue, Error> {
+fn add_function(base: &Base, name: &str, func: &typed::Func) -> Result<LValue, Error> {
     let param_types: Result<_, _> = func
         .params
         .iter()
@@ -61,20 +188,28 @@ fn add_function(base: &Base, func: &Func) -> Result<LValue, Error> {
     let mut param_types = param_types?;
     let ret_ty = apply_type(&func.ret_type, base)?;
     let func_ty = typ::func(&mut param_types, ret_ty);
-    Ok(util::add_function(
-        base.module,
-        func.name.0.as_str(),
-        func_ty,
-    ))
+    let gen_func = base.define_function(name, func_ty)?;
+    if func.visibility == Visibility::Public {
+        // may be called from C, or from other units which extend narrow integers as C does
+        abi::add_extension_attributes(gen_func, base);
+    }
+    Ok(gen_func)
 }
 
 fn add_function_body(
     base: &Base,
     gen_func: LValue,
-    func: &Func,
+    symbol: &str,
+    func: &typed::Func,
     env: &Env<LValue>,
 ) -> Result<(), Error> {
     util::add_entry_block(gen_func, base);
+    if let Some(ref debug_info) = base.debug_info {
+        let params = func.params.iter().map(|param| param.1.clone()).collect();
+        let func_ty = Type::Func(params, box func.ret_type.clone(), false);
+        let is_local = func.visibility == Visibility::Private;
+        debug_info.enter_function(gen_func, &func.name.0, symbol, &func_ty, is_local, base)?;
+    }
     let mut env = env.clone();
     let param_types: Result<_, _> = func
         .params
@@ -85,182 +220,366 @@ fn add_function_body(
 
     for (i, param) in func.params.iter().enumerate() {
         let typ = param_types[i];
-        let var = build::declare(
-            (param.0).0.as_str(),
-            typ,
-            util::get_func_param(gen_func, i),
-            base.builder,
-        );
+        let name = base.source_name(&param.0);
+        let var = build::declare(name, typ, util::get_func_param(gen_func, i), base.builder());
+        if let Some(ref debug_info) = base.debug_info {
+            debug_info.declare_variable(name, var, &param.1, Some(i + 1), base)?;
+        }
         env = env.add(param.0.clone(), var);
     }
 
     let expr = apply_expr(&func.body, &env, base)?;
-    build::ret(expr, base.builder);
+    build::ret(expr, base.builder());
+    if let Some(ref debug_info) = base.debug_info {
+        debug_info.leave_function(base);
+    }
     Ok(())
 }
 
-fn apply_expr(e: &Expr, env: &Env<LValue>, base: &Base) -> Result<LValue, Error> {
-    match e {
-        Expr::Const(ref lit) => apply_literal(lit, env, base),
-        Expr::Let(ref name, ref typ, box ref e1, box ref e2) => {
+fn apply_expr(e: &typed::Expr, env: &Env<LValue>, base: &Base) -> Result<LValue, Error> {
+    use typed::ExprKind;
+    match e.kind {
+        ExprKind::Const(ref lit) => apply_literal(lit, &e.typ, env, base)?.into_value(base),
+        ExprKind::Let(ref name, ref typ, box ref e1, box ref e2) => {
             if typ == &Type::Void {
                 apply_expr(e1, env, base)?;
                 apply_expr(e2, env, base)
             } else {
-                let typ = if let Type::Func(from, box to) = typ {
-                    Type::Pointer(box Type::Func(from.clone(), box to.clone()))
+                let typ = if let Type::Func(from, box to, is_variadic) = typ {
+                    Type::Pointer(box Type::Func(from.clone(), box to.clone(), *is_variadic))
                 } else {
                     typ.clone()
                 };
                 let l_typ = apply_type(&typ, base)?;
                 let l_e1 = apply_expr(e1, env, base)?;
-                let var = build::declare(&name.0, l_typ, l_e1, base.builder);
+                let source_name = base.source_name(name);
+                let var = build::declare(source_name, l_typ, l_e1, base.builder());
+                if let Some(ref debug_info) = base.debug_info {
+                    debug_info.declare_variable(source_name, var, &typ, None, base)?;
+                }
                 let env = env.add(name.clone(), var);
                 apply_expr(e2, &env, base)
             }
         }
-        Expr::Var(ref name) => env
+        ExprKind::Var(ref name) => env
             .lookup(name)
             .ok_or(Error::Internal(format!("unbound variable: {}", name))),
-        Expr::Load(box ref e) => {
+        ExprKind::Load(box ref e) => {
             let e = apply_expr(e, env, base)?;
-            Ok(build::load(e, base.builder))
+            Ok(build::load(e, base.builder()))
         }
-        Expr::Assign(box ref e1, box ref e2) => {
+        ExprKind::Assign(box ref e1, box ref e2) => {
             let lhs = apply_expr(e1, env, base)?;
             let rhs = apply_expr(e2, env, base)?;
-            Ok(build::store(lhs, rhs, base.builder))
+            Ok(build::store(lhs, rhs, base.builder()))
         }
-        Expr::Call(box ref func, ref args) => {
+        ExprKind::Call(box ref func, ref args) => {
+            let params_len = match func.typ {
+                Type::Pointer(box Type::Func(ref params, _, _)) => params.len(),
+                _ => args.len(),
+            };
             let func = apply_expr(func, env, base)?;
-            let args: Result<_, _> = args.iter().map(|arg| apply_expr(arg, env, base)).collect();
-            let mut args = args?;
-            Ok(build::call(func, &mut args, base.builder))
+            let mut args_ = vec![];
+            for (i, arg) in args.iter().enumerate() {
+                let value = apply_expr(arg, env, base)?;
+                args_.push(if i < params_len {
+                    value
+                } else {
+                    promote_variadic_arg(value, &arg.typ, base)
+                });
+            }
+            let call = build::call(func, &mut args_, base.builder());
+            abi::add_call_site_attributes(call, func);
+            Ok(call)
+        }
+        ExprKind::If(box ref cond, box ref e1, box ref e2) => {
+            apply_if_expr(cond, e1, e2, &e.typ, env, base)
         }
-        Expr::If(box ref cond, box ref e1, box ref e2) => apply_if_expr(cond, e1, e2, env, base),
-        Expr::BinOp(op, box ref e1, box ref e2) => apply_binop_expr(op, e1, e2, env, base),
-        Expr::ArrayAt(box ref arr, box ref i) => apply_array_at(arr, i, env, base),
-        Expr::TupleAt(box ref e, ref idx) => apply_tuple_at(e, *idx, env, base),
-        Expr::PrintNum(box ref e) => apply_printnum_expr(e, env, base),
+        ExprKind::BinOp(ref op, box ref e1, box ref e2) => apply_binop_expr(op, e1, e2, env, base),
+        ExprKind::ArrayAt(box ref arr, box ref i) => apply_array_at(arr, i, env, base),
+        ExprKind::TupleAt(box ref e, ref idx) => apply_tuple_at(e, *idx, env, base),
+        ExprKind::PrintNum(box ref e) => apply_printnum_expr(e, env, base),
     }
 }
 
-fn apply_literal(lit: &Literal, env: &Env<LValue>, base: &Base) -> Result<LValue, Error> {
-    match lit {
-        Literal::Bool(b) => Ok(lit::bool(*b, base.context)),
-        Literal::Int(n) => Ok(lit::int32(*n, base.context)),
-        Literal::Char(c) => Ok(lit::char(*c, base.context)),
-        Literal::Array(ref arr, ref elem_ty) => {
-            let elem_ty = apply_type(elem_ty, base)?;
-            let arr: Result<_, _> = arr.iter().map(|e| apply_expr(e, env, base)).collect();
-            let arr = arr?;
-            Ok(lit::array(arr, elem_ty, base))
-        }
-        Literal::Tuple(ref elems) => {
-            let elems: Result<_, _> = elems.iter().map(|e| apply_expr(e, env, base)).collect();
-            let elems = elems?;
-            Ok(lit::tuple(elems, base))
+// default argument promotions of C
+fn promote_variadic_arg(value: LValue, typ: &Type, base: &Base) -> LValue {
+    match typ {
+        Type::Bool => build::zext(value, typ::int32(base.context()), base.builder()),
+        Type::Char => build::sext(value, typ::int32(base.context()), base.builder()),
+        _ => value,
+    }
+}
+
+// value of a literal, kept as a `Constant` as long as all of its elements are constants,
+// so that nested literals are evaluated once
+enum Operand {
+    Const(Constant),
+    Value(LValue),
+}
+
+impl Operand {
+    fn into_value(self, base: &Base) -> Result<LValue, Error> {
+        match self {
+            Operand::Const(c) => lit::constant(&c, base),
+            Operand::Value(value) => Ok(value),
         }
-        Literal::ExternalFunc(ref name, ref typ) => {
+    }
+}
+
+fn apply_operand(e: &typed::Expr, env: &Env<LValue>, base: &Base) -> Result<Operand, Error> {
+    if let typed::ExprKind::Const(ref lit) = e.kind {
+        return apply_literal(lit, &e.typ, env, base);
+    }
+    match is_constant::eval(e) {
+        Some(c) => Ok(Operand::Const(c)),
+        None => Ok(Operand::Value(apply_expr(e, env, base)?)),
+    }
+}
+
+// evaluates elements of an aggregate, materializing the constant ones only if some are not
+fn apply_elements(
+    elems: &[typed::Expr],
+    env: &Env<LValue>,
+    base: &Base,
+) -> Result<Result<Vec<Constant>, Vec<LValue>>, Error> {
+    let elems: Result<Vec<_>, _> = elems.iter().map(|e| apply_operand(e, env, base)).collect();
+    let elems = elems?;
+    if elems.iter().all(|e| matches!(e, Operand::Const(_))) {
+        let consts = elems.into_iter().filter_map(|e| match e {
+            Operand::Const(c) => Some(c),
+            Operand::Value(_) => None,
+        });
+        Ok(Ok(consts.collect()))
+    } else {
+        let values: Result<_, _> = elems.into_iter().map(|e| e.into_value(base)).collect();
+        Ok(Err(values?))
+    }
+}
+
+fn apply_literal(
+    lit: &typed::Literal,
+    typ: &Type,
+    env: &Env<LValue>,
+    base: &Base,
+) -> Result<Operand, Error> {
+    match lit {
+        typed::Literal::Bool(b) => Ok(Operand::Const(Constant::Bool(*b))),
+        typed::Literal::Int(n) => Ok(Operand::Const(Constant::Int(*n))),
+        typed::Literal::Char(c) => Ok(Operand::Const(Constant::Char(*c))),
+        typed::Literal::Array(ref arr, ref elem_ty) => match apply_elements(arr, env, base)? {
+            Ok(consts) => Ok(Operand::Const(Constant::Array(consts, elem_ty.clone()))),
+            Err(values) => {
+                let elem_ty = apply_type(elem_ty, base)?;
+                Ok(Operand::Value(lit::array(values, elem_ty, base)))
+            }
+        },
+        typed::Literal::Tuple(ref elems) => match apply_elements(elems, env, base)? {
+            Ok(consts) => Ok(Operand::Const(Constant::Tuple(consts))),
+            Err(values) => {
+                let typ = apply_type(typ, base)?;
+                Ok(Operand::Value(lit::tuple(values, typ, base)))
+            }
+        },
+        typed::Literal::ExternalFunc(ref name, ref typ, ref cc) => {
             let typ = apply_type(typ, base)?;
-            Ok(lit::external_func(name.clone(), typ, base.module))
+            Ok(Operand::Value(abi::external_func(name, typ, *cc, base)?))
         }
     }
 }
 
 fn apply_if_expr(
-    cond: &Expr,
-    e1: &Expr,
-    e2: &Expr,
+    cond: &typed::Expr,
+    e1: &typed::Expr,
+    e2: &typed::Expr,
+    typ: &Type,
     env: &Env<LValue>,
     base: &Base,
 ) -> Result<LValue, Error> {
+    let typ = apply_type(typ, base)?;
     let cond = apply_expr(cond, env, base)?;
-    let insertion_block = util::insertion_block(base.builder);
+    let insertion_block = util::insertion_block(base.builder());
     let then_block = append_block(insertion_block, base);
     let else_block = append_block(then_block, base);
     let merge_block = append_block(else_block, base);
 
-    build::cond_branch(cond, then_block, else_block, base.builder);
+    build::cond_branch(cond, then_block, else_block, base.builder());
 
     // code generation for then-block
-    util::position_at_end(then_block, base.builder);
+    util::position_at_end(then_block, base.builder());
     let e1 = apply_expr(e1, env, base)?;
-    build::branch(merge_block, base.builder);
-    let then_block = util::insertion_block(base.builder);
+    build::branch(merge_block, base.builder());
+    let then_block = util::insertion_block(base.builder());
 
     // code generation for else-block
-    util::position_at_end(else_block, base.builder);
+    util::position_at_end(else_block, base.builder());
     let e2 = apply_expr(e2, env, base)?;
-    build::branch(merge_block, base.builder);
-    let else_block = util::insertion_block(base.builder);
+    build::branch(merge_block, base.builder());
+    let else_block = util::insertion_block(base.builder());
 
     // code generation for merge-block
-    util::position_at_end(merge_block, base.builder);
+    util::position_at_end(merge_block, base.builder());
     Ok(build::phi(
-        typ::type_of(e1),
+        typ,
         vec![(e1, then_block), (e2, else_block)],
-        base.builder,
+        base.builder(),
     ))
 }
 
 fn apply_binop_expr(
     op: &BinOp,
-    e1: &Expr,
-    e2: &Expr,
+    e1: &typed::Expr,
+    e2: &typed::Expr,
     env: &Env<LValue>,
     base: &Base,
 ) -> Result<LValue, Error> {
     let e1 = apply_expr(e1, env, base)?;
     let e2 = apply_expr(e2, env, base)?;
     match op {
-        BinOp::Add => Ok(build::add(e1, e2, base.builder)),
-        BinOp::Sub => Ok(build::sub(e1, e2, base.builder)),
-        BinOp::Mult => Ok(build::mult(e1, e2, base.builder)),
-        BinOp::Div => Ok(build::div(e1, e2, base.builder)),
-        BinOp::Eq => Ok(build::eq(e1, e2, base.builder)),
-        BinOp::Neq => Ok(build::neq(e1, e2, base.builder)),
-        BinOp::Lt => Ok(build::lt(e1, e2, base.builder)),
-        BinOp::Gt => Ok(build::gt(e1, e2, base.builder)),
-        BinOp::Leq => Ok(build::leq(e1, e2, base.builder)),
-        BinOp::Geq => Ok(build::geq(e1, e2, base.builder)),
+        BinOp::Add | BinOp::Sub | BinOp::Mult => apply_arithmetic(op, e1, e2, base),
+        BinOp::Div => apply_div(e1, e2, base),
+        BinOp::Eq => Ok(build::eq(e1, e2, base.builder())),
+        BinOp::Neq => Ok(build::neq(e1, e2, base.builder())),
+        BinOp::Lt => Ok(build::lt(e1, e2, base.builder())),
+        BinOp::Gt => Ok(build::gt(e1, e2, base.builder())),
+        BinOp::Leq => Ok(build::leq(e1, e2, base.builder())),
+        BinOp::Geq => Ok(build::geq(e1, e2, base.builder())),
+    }
+}
+
+fn apply_arithmetic(op: &BinOp, lhs: LValue, rhs: LValue, base: &Base) -> Result<LValue, Error> {
+    let builder = base.builder();
+    match (base.arithmetic, op) {
+        (Arithmetic::Wrapping, BinOp::Add) => Ok(build::add(lhs, rhs, builder)),
+        (Arithmetic::Wrapping, BinOp::Sub) => Ok(build::sub(lhs, rhs, builder)),
+        (Arithmetic::Wrapping, _) => Ok(build::mult(lhs, rhs, builder)),
+        (Arithmetic::NoSignedWrap, BinOp::Add) => Ok(build::nsw_add(lhs, rhs, builder)),
+        (Arithmetic::NoSignedWrap, BinOp::Sub) => Ok(build::nsw_sub(lhs, rhs, builder)),
+        (Arithmetic::NoSignedWrap, _) => Ok(build::nsw_mult(lhs, rhs, builder)),
+        (Arithmetic::Checked, _) => {
+            let (intrinsic, msg) = match op {
+                BinOp::Add => ("sadd", "overflow in addition"),
+                BinOp::Sub => ("ssub", "overflow in subtraction"),
+                _ => ("smul", "overflow in multiplication"),
+            };
+            let (result, overflowed) = build::with_overflow(intrinsic, lhs, rhs, base)?;
+            let ok = build::eq(overflowed, lit::bool(false, base.context()), builder);
+            branch_to_trap(ok, base, |base| build::builtin::arithmetic_error(msg, base))?;
+            Ok(result)
+        }
+    }
+}
+
+fn apply_div(lhs: LValue, rhs: LValue, base: &Base) -> Result<LValue, Error> {
+    let builder = base.builder();
+    if base.arithmetic == Arithmetic::Checked {
+        let int = |n| lit::int32(n, base.context());
+        let ok = build::neq(rhs, int(0), builder);
+        branch_to_trap(ok, base, |base| {
+            build::builtin::arithmetic_error("division by zero", base)
+        })?;
+        let overflowed = build::and(
+            build::eq(lhs, int(i32::MIN), builder),
+            build::eq(rhs, int(-1), builder),
+            builder,
+        );
+        let ok = build::eq(overflowed, lit::bool(false, base.context()), builder);
+        branch_to_trap(ok, base, |base| {
+            build::builtin::arithmetic_error("overflow in division", base)
+        })?;
+    }
+    Ok(build::div(lhs, rhs, builder))
+}
+
+fn apply_array_at(
+    arr: &typed::Expr,
+    idx: &typed::Expr,
+    env: &Env<LValue>,
+    base: &Base,
+) -> Result<LValue, Error> {
+    let len = match arr.typ {
+        Type::Pointer(box Type::Array(_, len)) => len,
+        _ => return Err(Error::Internal(format!("not an array: {}", arr.typ))),
+    };
+    let l_arr = apply_expr(arr, env, base)?;
+    let l_idx = apply_expr(idx, env, base)?;
+    if base.bounds_checks && !is_in_bounds(idx, len) {
+        check_bounds(l_idx, len, base)?;
+    }
+    Ok(build::gep(l_arr, l_idx, base))
+}
+
+fn is_in_bounds(idx: &typed::Expr, len: usize) -> bool {
+    match is_constant::eval(idx) {
+        Some(Constant::Int(n)) => n >= 0 && (n as usize) < len,
+        _ => false,
     }
 }
 
-fn apply_array_at(arr: &Expr, idx: &Expr, env: &Env<LValue>, base: &Base) -> Result<LValue, Error> {
-    let arr = apply_expr(arr, env, base)?;
-    let idx = apply_expr(idx, env, base)?;
-    Ok(build::gep(arr, idx, base))
+// branches to a trap unless 0 <= idx < len
+fn check_bounds(idx: LValue, len: usize, base: &Base) -> Result<(), Error> {
+    let len = lit::int32(len as i32, base.context());
+    // negative indices are greater than any length as unsigned
+    let in_bounds = build::ult(idx, len, base.builder());
+    branch_to_trap(in_bounds, base, |base| {
+        build::builtin::out_of_bounds(idx, len, base)
+    })
 }
 
-fn apply_tuple_at(e: &Expr, idx: usize, env: &Env<LValue>, base: &Base) -> Result<LValue, Error> {
+// generates `trap` in a block taken unless `ok` holds, and continues in another block
+fn branch_to_trap<F>(ok: LValue, base: &Base, trap: F) -> Result<(), Error>
+where
+    F: FnOnce(&Base) -> Result<(), Error>,
+{
+    let insertion_block = util::insertion_block(base.builder());
+    let fail_block = append_block(insertion_block, base);
+    let ok_block = append_block(fail_block, base);
+    build::cond_branch(ok, ok_block, fail_block, base.builder());
+
+    util::position_at_end(fail_block, base.builder());
+    trap(base)?;
+
+    util::position_at_end(ok_block, base.builder());
+    Ok(())
+}
+
+fn apply_tuple_at(
+    e: &typed::Expr,
+    idx: usize,
+    env: &Env<LValue>,
+    base: &Base,
+) -> Result<LValue, Error> {
     let e = apply_expr(e, env, base)?;
     Ok(build::tuple_gep(e, idx as i32, base))
 }
 
-fn apply_printnum_expr(e: &Expr, env: &Env<LValue>, base: &Base) -> Result<LValue, Error> {
+fn apply_printnum_expr(e: &typed::Expr, env: &Env<LValue>, base: &Base) -> Result<LValue, Error> {
     let e = apply_expr(e, env, base)?;
-    Ok(build::builtin::print_num(e, base))
+    build::builtin::print_num(e, base)
 }
 
 fn apply_type(ty: &Type, base: &Base) -> Result<LType, Error> {
     match ty {
-        Type::Void => Ok(typ::void(base.context)),
-        Type::Bool => Ok(typ::bool(base.context)),
-        Type::Char => Ok(typ::char(base.context)),
-        Type::Int => Ok(typ::int32(base.context)),
-        Type::Func(ref params, box ret_ty) => {
+        Type::Void => Ok(typ::void(base.context())),
+        Type::Bool => Ok(typ::bool(base.context())),
+        Type::Char => Ok(typ::char(base.context())),
+        Type::Int => Ok(typ::int32(base.context())),
+        Type::Func(ref params, box ret_ty, is_variadic) => {
             let params: Result<_, _> = params.iter().map(|ty| apply_type(ty, base)).collect();
             let mut params = params?;
             let ret_ty = apply_type(ret_ty, base)?;
-            Ok(typ::func(&mut params, ret_ty))
+            if *is_variadic {
+                Ok(typ::variadic_func(&mut params, ret_ty))
+            } else {
+                Ok(typ::func(&mut params, ret_ty))
+            }
         }
         Type::Array(box ref elem_ty, ref len) => Ok(typ::array(apply_type(elem_ty, base)?, *len)),
         Type::Pointer(box ref ty) => Ok(typ::ptr(apply_type(ty, base)?)),
         Type::Tuple(ref elems) => {
             let elems: Result<_, _> = elems.iter().map(|ty| apply_type(ty, base)).collect();
             let elems = elems?;
-            Ok(typ::tuple(elems))
+            Ok(typ::tuple(elems, base.context()))
         }
     }
 }
@@ -0,0 +1,345 @@
+use super::*;
+use llvm::core::*;
+use llvm::{LLVMAttributeFunctionIndex, LLVMCallConv, LLVMLinkage, LLVMTypeKind};
+
+// how a value of nf type crosses the boundary of a C function,
+// following the System V x86-64 ABI for aggregates of integer class,
+// which are rejected on other targets.
+// Integers narrower than `int` are passed as they are, and extended by `extension`.
+#[derive(Clone, Copy)]
+enum Lowering {
+    Direct,
+    Coerce(LType),
+    Indirect,
+}
+
+/// Declares the external function `name`, whose nf-level type is `typ`.
+///
+/// When the C ABI passes some of its aggregate params or its return value differently
+/// from LLVM first-class aggregates, or when `cc` is not the default one, the external
+/// declaration is lowered accordingly and a private wrapper of type `typ` is returned,
+/// so that the function can be used as any other nf function.
+/// Arrays can not be passed nor returned by value in C, so they are rejected.
+pub fn external_func(
+    name: &str,
+    typ: LType,
+    cc: CallConv,
+    base: &Base,
+) -> Result<LValue, Error> {
+    let ret_ty = unsafe { LLVMGetReturnType(typ) };
+    let param_types = param_types(typ);
+    let is_variadic = unsafe { LLVMIsFunctionVarArg(typ) != 0 };
+
+    let (ret, params): (Lowering, Vec<Lowering>) = if cc == CallConv::C {
+        let params: Result<_, _> = param_types
+            .iter()
+            .map(|ty| classify(*ty, name, base))
+            .collect();
+        (classify(ret_ty, name, base)?, params?)
+    } else {
+        (Lowering::Direct, vec![Lowering::Direct; param_types.len()])
+    };
+    let attrs = attributes(ret_ty, &ret, &param_types, &params, base);
+    let is_direct = |lowering: &Lowering| matches!(lowering, Lowering::Direct);
+    if cc == CallConv::C && is_direct(&ret) && params.iter().all(is_direct) {
+        // call sites take the attributes from the declaration, see `add_call_site_attributes`
+        let func = declare_external(name, typ, cc, base)?;
+        for (idx, attr) in attrs.iter() {
+            unsafe { LLVMAddAttributeAtIndex(func, *idx, *attr) }
+        }
+        return Ok(func);
+    }
+    // variadic args can not be forwarded by a wrapper
+    if is_variadic {
+        return Err(Error::Unsupported(format!(
+            "variadic external function {} must use scalars and C calling convention",
+            name
+        )));
+    }
+
+    // declaration of the C function
+    let mut c_param_types = vec![];
+    if let Lowering::Indirect = ret {
+        c_param_types.push(typ::ptr(ret_ty));
+    }
+    for (ty, lowering) in param_types.iter().zip(params.iter()) {
+        c_param_types.push(match lowering {
+            Lowering::Direct => *ty,
+            Lowering::Coerce(ty) => *ty,
+            Lowering::Indirect => typ::ptr(*ty),
+        });
+    }
+    let c_ret_ty = match ret {
+        Lowering::Direct => ret_ty,
+        Lowering::Coerce(ty) => ty,
+        Lowering::Indirect => typ::void(base.context()),
+    };
+    let c_func = declare_external(name, typ::func(&mut c_param_types, c_ret_ty), cc, base)?;
+    let wrapper_name = format!("{}.wrapper", name);
+    if let Some(wrapper) = base.lookup_function(&wrapper_name) {
+        if typ::type_of(wrapper) != typ::ptr(typ) {
+            return Err(Error::ConflictingDeclaration(name.to_string()));
+        }
+        return Ok(wrapper);
+    }
+    for (idx, attr) in attrs.iter() {
+        unsafe { LLVMAddAttributeAtIndex(c_func, *idx, *attr) }
+    }
+
+    // wrapper with nf calling convention
+    let wrapper = base.define_function(&wrapper_name, typ)?;
+    util::set_linkage(wrapper, LLVMLinkage::LLVMPrivateLinkage);
+    unsafe {
+        LLVMAddAttributeAtIndex(
+            wrapper,
+            LLVMAttributeFunctionIndex,
+            enum_attribute("alwaysinline", base),
+        );
+        // the builder of `base` may be in the middle of another function
+        let owned_builder = base.new_builder();
+        let builder = owned_builder.as_raw();
+        let entry = LLVMAppendBasicBlockInContext(
+            base.context(),
+            wrapper,
+            b"entry\0".as_ptr() as *const _,
+        );
+        LLVMPositionBuilderAtEnd(builder, entry);
+
+        let mut args = vec![];
+        let sret = if let Lowering::Indirect = ret {
+            let sret = LLVMBuildAlloca(builder, ret_ty, b"\0".as_ptr() as *const _);
+            args.push(sret);
+            Some(sret)
+        } else {
+            None
+        };
+        for (i, (ty, lowering)) in param_types.iter().zip(params.iter()).enumerate() {
+            let param = util::get_func_param(wrapper, i);
+            args.push(match lowering {
+                Lowering::Direct => param,
+                Lowering::Coerce(c_ty) => {
+                    let tmp = LLVMBuildAlloca(builder, *c_ty, b"\0".as_ptr() as *const _);
+                    build::store(bit_cast(tmp, typ::ptr(*ty), builder), param, builder);
+                    build::load(tmp, builder)
+                }
+                Lowering::Indirect => {
+                    let tmp = LLVMBuildAlloca(builder, *ty, b"\0".as_ptr() as *const _);
+                    build::store(tmp, param, builder);
+                    tmp
+                }
+            });
+        }
+
+        let result = build::call(c_func, &mut args, builder);
+        LLVMSetInstructionCallConv(result, call_conv(cc) as libc::c_uint);
+        for (idx, attr) in attrs.iter() {
+            LLVMAddCallSiteAttribute(result, *idx, *attr);
+        }
+
+        match (ret, sret) {
+            (Lowering::Indirect, Some(sret)) => build::ret(build::load(sret, builder), builder),
+            (Lowering::Coerce(c_ty), _) => {
+                let tmp = LLVMBuildAlloca(builder, c_ty, b"\0".as_ptr() as *const _);
+                build::store(tmp, result, builder);
+                build::ret(
+                    build::load(bit_cast(tmp, typ::ptr(ret_ty), builder), builder),
+                    builder,
+                );
+            }
+            _ if LLVMGetTypeKind(ret_ty) == LLVMTypeKind::LLVMVoidTypeKind => {
+                LLVMBuildRetVoid(builder);
+            }
+            _ => build::ret(result, builder),
+        }
+    }
+    Ok(wrapper)
+}
+
+// declarations of the same name must agree on the calling convention as well as the type
+fn declare_external(name: &str, typ: LType, cc: CallConv, base: &Base) -> Result<LValue, Error> {
+    let is_declared = base.lookup_function(name).is_some();
+    let func = base.declare_function(name, typ)?;
+    let cc = call_conv(cc) as libc::c_uint;
+    if !is_declared {
+        unsafe { LLVMSetFunctionCallConv(func, cc) }
+    } else if unsafe { LLVMGetFunctionCallConv(func) } != cc {
+        return Err(Error::ConflictingCallConv(name.to_string()));
+    }
+    Ok(func)
+}
+
+fn classify(typ: LType, name: &str, base: &Base) -> Result<Lowering, Error> {
+    unsafe {
+        match LLVMGetTypeKind(typ) {
+            LLVMTypeKind::LLVMStructTypeKind => (),
+            LLVMTypeKind::LLVMArrayTypeKind => {
+                return Err(Error::Unsupported(format!(
+                    "arrays can not be passed to nor returned from external function {} by value",
+                    name
+                )))
+            }
+            _ => return Ok(Lowering::Direct),
+        }
+        let triple = util::get_target(base.module());
+        if !is_sysv_x86_64(&triple) {
+            return Err(Error::Unsupported(format!(
+                "aggregates can not be passed to nor returned from external function {} \
+                 by value on {}",
+                name,
+                if triple.is_empty() { "an unknown target" } else { triple.as_str() }
+            )));
+        }
+        // sizes would be those of the default layout otherwise
+        if util::get_data_layout(base.module()).is_empty() {
+            return Err(Error::Target(format!(
+                "the data layout must be set to lower the aggregates of external function {}",
+                name
+            )));
+        }
+        let data_layout = llvm::target::LLVMGetModuleDataLayout(base.module());
+        let size = llvm::target::LLVMABISizeOfType(data_layout, typ) as libc::c_uint;
+        Ok(if size == 0 {
+            Lowering::Direct
+        } else if size > 16 {
+            Lowering::Indirect
+        } else if size <= 8 {
+            Lowering::Coerce(LLVMIntTypeInContext(base.context(), size * 8))
+        } else {
+            let eightbytes = vec![
+                LLVMInt64TypeInContext(base.context()),
+                LLVMIntTypeInContext(base.context(), (size - 8) * 8),
+            ];
+            Lowering::Coerce(typ::tuple(eightbytes, base.context()))
+        })
+    }
+}
+
+// Windows has its own x86-64 calling convention
+fn is_sysv_x86_64(triple: &str) -> bool {
+    let mut parts = triple.split('-');
+    parts.next() == Some("x86_64")
+        && !parts.any(|part| ["windows", "win32", "mingw"].iter().any(|os| part.starts_with(os)))
+}
+
+// indices of attributes for both the declaration and call sites
+fn attributes(
+    ret_ty: LType,
+    ret: &Lowering,
+    param_types: &[LType],
+    params: &[Lowering],
+    base: &Base,
+) -> Vec<(llvm::LLVMAttributeIndex, llvm::prelude::LLVMAttributeRef)> {
+    let mut attrs = vec![];
+    // parameter indices start from 1
+    let mut offset = 1;
+    match ret {
+        Lowering::Direct => {
+            if let Some(ext) = extension(ret_ty) {
+                attrs.push((llvm::LLVMAttributeReturnIndex, enum_attribute(ext, base)));
+            }
+        }
+        Lowering::Coerce(_) => (),
+        Lowering::Indirect => {
+            attrs.push((1, enum_attribute("sret", base)));
+            offset += 1;
+        }
+    }
+    for (i, (ty, lowering)) in param_types.iter().zip(params.iter()).enumerate() {
+        let idx = (i + offset) as libc::c_uint;
+        match lowering {
+            Lowering::Direct => {
+                if let Some(ext) = extension(*ty) {
+                    attrs.push((idx, enum_attribute(ext, base)));
+                }
+            }
+            Lowering::Coerce(_) => (),
+            Lowering::Indirect => attrs.push((idx, enum_attribute("byval", base))),
+        }
+    }
+    attrs
+}
+
+/// The attribute by which C extends an integer of type `typ` narrower than `int` to a whole
+/// register: `bool` by zeros, and `char` by its sign as nf chars are `signed char`.
+pub(crate) fn extension(typ: LType) -> Option<&'static str> {
+    unsafe {
+        if LLVMGetTypeKind(typ) != LLVMTypeKind::LLVMIntegerTypeKind {
+            return None;
+        }
+        match LLVMGetIntTypeWidth(typ) {
+            1 => Some("zeroext"),
+            width if width < 32 => Some("signext"),
+            _ => None,
+        }
+    }
+}
+
+/// Adds `extension` attributes to the return value and the params of `func`,
+/// which is called with the C ABI.
+pub(crate) fn add_extension_attributes(func: LValue, base: &Base) {
+    unsafe {
+        let func_ty = LLVMGetElementType(LLVMTypeOf(func));
+        let mut types = vec![(llvm::LLVMAttributeReturnIndex, LLVMGetReturnType(func_ty))];
+        // parameter indices start from 1
+        for i in 0..LLVMCountParams(func) {
+            types.push((i + 1, LLVMTypeOf(LLVMGetParam(func, i))));
+        }
+        for (idx, typ) in types {
+            if let Some(ext) = extension(typ) {
+                LLVMAddAttributeAtIndex(func, idx, enum_attribute(ext, base));
+            }
+        }
+    }
+}
+
+/// Adds the attributes of the return value and the params of `func` to `call`,
+/// so that the caller extends narrow integers as the callee expects.
+/// Calls through pointers are left as they are, since their callee is not known.
+pub(crate) fn add_call_site_attributes(call: LValue, func: LValue) {
+    unsafe {
+        if LLVMIsAFunction(func).is_null() {
+            return;
+        }
+        let indices = Some(llvm::LLVMAttributeReturnIndex)
+            .into_iter()
+            .chain(1..=LLVMCountParams(func));
+        for idx in indices {
+            let len = LLVMGetAttributeCountAtIndex(func, idx) as usize;
+            let mut attrs = vec![std::ptr::null_mut(); len];
+            LLVMGetAttributesAtIndex(func, idx, attrs.as_mut_ptr());
+            for attr in attrs {
+                LLVMAddCallSiteAttribute(call, idx, attr);
+            }
+        }
+    }
+}
+
+pub(crate) fn enum_attribute(name: &str, base: &Base) -> llvm::prelude::LLVMAttributeRef {
+    unsafe {
+        let kind = LLVMGetEnumAttributeKindForName(name.as_ptr() as *const _, name.len());
+        LLVMCreateEnumAttribute(base.context(), kind, 0)
+    }
+}
+
+fn param_types(typ: LType) -> Vec<LType> {
+    unsafe {
+        let len = LLVMCountParamTypes(typ) as usize;
+        let mut params = vec![std::ptr::null_mut(); len];
+        LLVMGetParamTypes(typ, params.as_mut_ptr());
+        params
+    }
+}
+
+fn bit_cast(value: LValue, typ: LType, builder: LBuilder) -> LValue {
+    unsafe { LLVMBuildBitCast(builder, value, typ, b"\0".as_ptr() as *const _) }
+}
+
+fn call_conv(cc: CallConv) -> LLVMCallConv {
+    match cc {
+        CallConv::C => LLVMCallConv::LLVMCCallConv,
+        CallConv::Fast => LLVMCallConv::LLVMFastCallConv,
+        CallConv::Cold => LLVMCallConv::LLVMColdCallConv,
+        CallConv::X86StdCall => LLVMCallConv::LLVMX86StdcallCallConv,
+        CallConv::X86FastCall => LLVMCallConv::LLVMX86FastcallCallConv,
+    }
+}
@@ -1,4 +1,7 @@
 use super::*;
+use crate::session::Options;
+use llvm::LLVMLinkage;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::CString;
 
@@ -9,62 +12,370 @@ pub type LType = llvm::prelude::LLVMTypeRef;
 pub type LValue = llvm::prelude::LLVMValueRef;
 pub type LBasicBlock = llvm::prelude::LLVMBasicBlockRef;
 
-pub struct Base {
-    pub context: LContext,
-    pub module: LModule,
-    pub builder: LBuilder,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputKind {
+    /// `main` is synthesized from the body of `Nf`.
+    Executable,
+    /// The body of `Nf` is ignored and public functions get external linkage.
+    Library,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arithmetic {
+    /// `+`, `-` and `*` wrap around on overflow.
+    /// `/` is undefined behavior if the divisor is zero or the division overflows.
+    #[default]
+    Wrapping,
+    /// Same as `Wrapping`, but overflow of `+`, `-` and `*` is undefined behavior too,
+    /// which lets LLVM optimize more.
+    NoSignedWrap,
+    /// Overflow and division by zero abort the program with a diagnostic.
+    Checked,
+}
+
+/// Generation state of one module.
+///
+/// An LLVM context may be used by only one thread at a time, so `Base` is neither `Send`
+/// nor `Sync`. To generate on several threads, give each thread a `Context` of its own,
+/// and send the results as `Module`s.
+pub struct Base<'ctx> {
     pub struct_env: HashMap<String, LType>,
+    /// Names given by `resolve` or `opt` to the source names they stand for, which are
+    /// used for values in the IR and variables in debug info.
+    pub source_names: HashMap<Ident, Ident>,
+    pub symbol_prefix: String,
+    pub linkage: LLVMLinkage,
+    pub output: OutputKind,
+    pub debug_info: Option<DebugInfo>,
+    pub opt_level: OptLevel,
+    /// Whether to check that indices of arrays are in bounds at runtime.
+    pub bounds_checks: bool,
+    pub arithmetic: Arithmetic,
+    functions: RefCell<HashMap<String, LValue>>,
+    // disposed in this order, after the debug info which refers to the module
+    builder: Builder<'ctx>,
+    module: OwnedModule<'ctx>,
+    context: &'ctx Context,
 }
 
-impl Base {
-    pub fn new(name: &str) -> Base {
-        unsafe {
-            let context = llvm::core::LLVMContextCreate();
-            let name = CString::new(name).unwrap();
-            let module = llvm::core::LLVMModuleCreateWithNameInContext(name.as_ptr(), context);
-            let builder = llvm::core::LLVMCreateBuilderInContext(context);
-
-            self::add_builtin(context, module);
-            Base {
-                context,
-                module,
-                builder,
-                struct_env: HashMap::new(),
+impl<'ctx> Base<'ctx> {
+    /// Creates a new module named `name` in `context`.
+    pub fn new(context: &'ctx Context, name: &str) -> Base<'ctx> {
+        Base::in_context(context, OwnedModule::new(name, context))
+    }
+
+    /// Creates a new module configured by `options`.
+    /// The module is generated for the host unless another target is given.
+    pub fn with_options(
+        context: &'ctx Context,
+        name: &str,
+        options: &Options,
+    ) -> Result<Base<'ctx>, Error> {
+        let mut base = Base::new(context, name);
+        let triple = match options.target {
+            Some(ref triple) => triple.clone(),
+            None => host_triple(),
+        };
+        let machine = TargetMachine::new(
+            &triple,
+            &options.cpu,
+            &options.features,
+            options.opt_level,
+        )?;
+        machine.configure(base.module());
+        base.opt_level = options.opt_level;
+        base.output = options.output;
+        base.bounds_checks = options.bounds_checks;
+        base.arithmetic = options.arithmetic;
+        if let Some(ref source) = options.debug_info {
+            base.enable_debug_info(&source.filename, &source.directory)?;
+        }
+        Ok(base)
+    }
+
+    /// Generates into `module`, which may have functions in it already,
+    /// and is given back by `into_module`. `module` must have been created in `context`.
+    pub fn with_module(
+        context: &'ctx Context,
+        module: OwnedModule<'ctx>,
+    ) -> Result<Base<'ctx>, Error> {
+        if unsafe { llvm::core::LLVMGetModuleContext(module.as_raw()) } != context.as_raw() {
+            return Err(Error::Link(
+                "a module in another context can not be generated into".to_string(),
+            ));
+        }
+        Ok(Base::in_context(context, module))
+    }
+
+    // `module` belongs to `context`
+    fn in_context(context: &'ctx Context, module: OwnedModule<'ctx>) -> Base<'ctx> {
+        Base {
+            struct_env: HashMap::new(),
+            source_names: HashMap::new(),
+            symbol_prefix: String::new(),
+            linkage: LLVMLinkage::LLVMExternalLinkage,
+            output: OutputKind::Executable,
+            debug_info: None,
+            opt_level: OptLevel::O0,
+            bounds_checks: false,
+            arithmetic: Arithmetic::Wrapping,
+            functions: RefCell::new(HashMap::new()),
+            builder: Builder::new(context),
+            module,
+            context,
+        }
+    }
+
+    pub(crate) fn context(&self) -> LContext {
+        self.context.as_raw()
+    }
+
+    pub(crate) fn module(&self) -> LModule {
+        self.module.as_raw()
+    }
+
+    pub(crate) fn builder(&self) -> LBuilder {
+        self.builder.as_raw()
+    }
+
+    // a builder of its own, for a function generated while the builder of `self` is in the
+    // middle of another one
+    pub(crate) fn new_builder(&self) -> Builder<'ctx> {
+        Builder::new(self.context)
+    }
+
+    /// Emits DWARF debug info for the generated functions, as if they were in `filename`.
+    /// The data layout of the module must be set, as the sizes of types are taken from it.
+    pub fn enable_debug_info(&mut self, filename: &str, directory: &str) -> Result<(), Error> {
+        if util::get_data_layout(self.module()).is_empty() {
+            return Err(Error::Target(
+                "the data layout must be set to emit debug info".to_string(),
+            ));
+        }
+        self.debug_info = Some(DebugInfo::new(
+            self.module(),
+            self.context(),
+            filename,
+            directory,
+        ));
+        Ok(())
+    }
+
+    pub fn source_name<'a>(&'a self, name: &'a Ident) -> &'a str {
+        &self.source_names.get(name).unwrap_or(name).0
+    }
+
+    pub fn symbol_name(&self, name: &Ident) -> String {
+        format!("{}{}", self.symbol_prefix, name)
+    }
+
+    /// Returns the function `name` of the module, declaring it if absent.
+    /// Declarations of the same name are shared, and must have the same type.
+    pub fn declare_function(&self, name: &str, typ: LType) -> Result<LValue, Error> {
+        if let Some(func) = self.lookup_function(name) {
+            if typ::type_of(func) == typ::ptr(typ) {
+                Ok(func)
+            } else {
+                Err(Error::ConflictingDeclaration(name.to_string()))
             }
+        } else {
+            let func = util::add_function(self.module(), name, typ);
+            self.functions.borrow_mut().insert(name.to_string(), func);
+            Ok(func)
         }
     }
-}
 
-impl Drop for Base {
-    fn drop(&mut self) {
-        use llvm::core::*;
-        unsafe {
-            LLVMDisposeBuilder(self.builder);
-            LLVMDisposeModule(self.module);
-            LLVMContextDispose(self.context);
+    /// Same as `declare_function`, but the function must not have a body yet.
+    pub fn define_function(&self, name: &str, typ: LType) -> Result<LValue, Error> {
+        let func = self.declare_function(name, typ)?;
+        if util::has_body(func) {
+            Err(Error::Redefinition(name.to_string()))
+        } else {
+            Ok(func)
+        }
+    }
+
+    pub fn lookup_function(&self, name: &str) -> Option<LValue> {
+        if let Some(func) = self.functions.borrow().get(name) {
+            return Some(*func);
+        }
+        // the module may be given by the caller with functions already in it
+        let c_name = CString::new(name).unwrap();
+        let func = unsafe { llvm::core::LLVMGetNamedFunction(self.module(), c_name.as_ptr()) };
+        if func.is_null() {
+            None
+        } else {
+            self.functions.borrow_mut().insert(name.to_string(), func);
+            Some(func)
+        }
+    }
+
+    /// Takes the generated module out of `self`.
+    pub fn into_module(mut self) -> OwnedModule<'ctx> {
+        self.debug_info.take();
+        self.module
+    }
+
+    /// Links the module of `src` into this one. `src` must share the context of `self`,
+    /// and its module is destroyed by linking.
+    pub fn link(&self, src: Base<'ctx>) -> Result<(), Error> {
+        if src.context() != self.context() {
+            return Err(Error::Link(
+                "modules in different contexts can not be linked".to_string(),
+            ));
         }
+        // the source module is destroyed by the linker whether it succeeds or not
+        util::link_modules(self.module(), src.into_module().into_raw())
     }
 }
 
-fn add_builtin(context: LContext, module: LModule) {
-    add_printf_function(context, module);
-    add_num_format_str(context, module);
+/// A generated module which owns its context.
+pub struct Module {
+    // disposed before the context
+    module: LModule,
+    _context: Context,
 }
 
-fn add_printf_function(context: LContext, module: LModule) {
-    let name = CString::new("printf").unwrap();
-    let typ = typ::variadic_func(&mut vec![typ::char_ptr(context)], typ::int32(context));
-    unsafe {
-        llvm::core::LLVMAddFunction(module, name.as_ptr(), typ);
+// nothing but the module refers to the context, so both can move to another thread together
+unsafe impl Send for Module {}
+
+impl Module {
+    /// Generates a module by `gen` in a new context, which the module takes with it.
+    pub(crate) fn generate<F>(gen: F) -> Result<Module, Error>
+    where
+        F: for<'ctx> FnOnce(&'ctx Context) -> Result<OwnedModule<'ctx>, Error>,
+    {
+        let context = Context::new();
+        let module = gen(&context)?.into_raw();
+        Ok(Module {
+            module,
+            _context: context,
+        })
+    }
+
+    pub fn as_raw(&self) -> LModule {
+        self.module
+    }
+
+    pub fn print(&self) -> Result<String, Error> {
+        util::print_module(self.as_raw())
     }
 }
 
-fn add_num_format_str(context: LContext, module: LModule) {
-    let num_format_str = CString::new(".builtin.format.num").unwrap();
-    let init = lit::str("%d\n", context);
-    unsafe {
-        let global_var =
-            llvm::core::LLVMAddGlobal(module, typ::type_of(init), num_format_str.as_ptr());
-        llvm::core::LLVMSetInitializer(global_var, init);
+impl Drop for Module {
+    fn drop(&mut self) {
+        unsafe { llvm::core::LLVMDisposeModule(self.module) }
+    }
+}
+
+impl<'ctx> Base<'ctx> {
+    // builtins are declared on first use, so that unused ones are not emitted
+    pub(crate) fn printf_function(&self) -> Result<LValue, Error> {
+        let typ = typ::variadic_func(
+            &mut vec![typ::char_ptr(self.context())],
+            typ::int32(self.context()),
+        );
+        self.declare_function("printf", typ)
+    }
+
+    pub(crate) fn num_format_str(&self) -> LValue {
+        self.global_str(".builtin.format.num", "%d\n")
+    }
+
+    // a constant string of the module, shared by the uses of the same name
+    pub(crate) fn global_str(&self, name: &str, value: &str) -> LValue {
+        let name = CString::new(name).unwrap();
+        unsafe {
+            let global_var = llvm::core::LLVMGetNamedGlobal(self.module(), name.as_ptr());
+            if !global_var.is_null() {
+                return global_var;
+            }
+            let init = lit::str(value, self.context());
+            let global_var =
+                llvm::core::LLVMAddGlobal(self.module(), typ::type_of(init), name.as_ptr());
+            llvm::core::LLVMSetInitializer(global_var, init);
+            llvm::core::LLVMSetGlobalConstant(global_var, 1);
+            // private so that linking several units does not clash on it
+            llvm::core::LLVMSetLinkage(global_var, LLVMLinkage::LLVMPrivateLinkage);
+            global_var
+        }
+    }
+
+    /// Returns `void .builtin.out_of_bounds(i8* func, i32 idx, i32 len)`, defining it if absent.
+    /// It prints where an index was out of bounds to stderr, and aborts.
+    pub(crate) fn out_of_bounds_function(&self) -> Result<LValue, Error> {
+        let int_ty = typ::int32(self.context());
+        let char_ptr = typ::char_ptr(self.context());
+        self.abort_function(
+            "out_of_bounds",
+            &mut vec![char_ptr, int_ty, int_ty],
+            "%s: index %d is out of bounds for length %d\n\0",
+        )
+    }
+
+    /// Returns `void .builtin.arithmetic_error(i8* func, i8* msg)`, defining it if absent.
+    /// It prints the error in the function to stderr, and aborts.
+    pub(crate) fn arithmetic_error_function(&self) -> Result<LValue, Error> {
+        let char_ptr = typ::char_ptr(self.context());
+        self.abort_function(
+            "arithmetic_error",
+            &mut vec![char_ptr, char_ptr],
+            "%s: %s\n\0",
+        )
+    }
+
+    // a builtin which prints its params to stderr in `format`, and aborts
+    fn abort_function(
+        &self,
+        name: &str,
+        params: &mut Vec<LType>,
+        format: &str,
+    ) -> Result<LValue, Error> {
+        use llvm::core::*;
+        let symbol = format!(".builtin.{}", name);
+        if let Some(func) = self.lookup_function(&symbol) {
+            return Ok(func);
+        }
+        let int_ty = typ::int32(self.context());
+        let char_ptr = typ::char_ptr(self.context());
+        let dprintf = self.declare_function(
+            "dprintf",
+            typ::variadic_func(&mut vec![int_ty, char_ptr], int_ty),
+        )?;
+        let abort =
+            self.declare_function("abort", typ::func(&mut vec![], typ::void(self.context())))?;
+        let func = self.define_function(&symbol, typ::func(params, typ::void(self.context())))?;
+        util::set_linkage(func, LLVMLinkage::LLVMPrivateLinkage);
+        for attr in ["noreturn", "cold", "noinline"].iter() {
+            unsafe {
+                LLVMAddAttributeAtIndex(
+                    func,
+                    llvm::LLVMAttributeFunctionIndex,
+                    abi::enum_attribute(attr, self),
+                );
+            }
+        }
+
+        let owned_builder = self.new_builder();
+        let builder = owned_builder.as_raw();
+        unsafe {
+            let entry = LLVMAppendBasicBlockInContext(
+                self.context(),
+                func,
+                b"entry\0".as_ptr() as *const _,
+            );
+            LLVMPositionBuilderAtEnd(builder, entry);
+            let format = self.global_str(&format!(".builtin.format.{}", name), format);
+            let format = LLVMBuildBitCast(builder, format, char_ptr, b"\0".as_ptr() as *const _);
+            let stderr = lit::int32(2, self.context());
+            let mut args = vec![stderr, format];
+            for i in 0..params.len() {
+                args.push(util::get_func_param(func, i));
+            }
+            build::call(dprintf, &mut args, builder);
+            build::call(abort, &mut vec![], builder);
+            build::unreachable(builder);
+        }
+        Ok(func)
     }
 }
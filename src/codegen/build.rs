@@ -41,10 +41,56 @@ pub fn mult(lhs: LValue, rhs: LValue, builder: LBuilder) -> LValue {
     unsafe { LLVMBuildMul(builder, lhs, rhs, b"\0".as_ptr() as *const _) }
 }
 
+pub fn nsw_add(lhs: LValue, rhs: LValue, builder: LBuilder) -> LValue {
+    unsafe { LLVMBuildNSWAdd(builder, lhs, rhs, b"\0".as_ptr() as *const _) }
+}
+
+pub fn nsw_sub(lhs: LValue, rhs: LValue, builder: LBuilder) -> LValue {
+    unsafe { LLVMBuildNSWSub(builder, lhs, rhs, b"\0".as_ptr() as *const _) }
+}
+
+pub fn nsw_mult(lhs: LValue, rhs: LValue, builder: LBuilder) -> LValue {
+    unsafe { LLVMBuildNSWMul(builder, lhs, rhs, b"\0".as_ptr() as *const _) }
+}
+
+/// Calls `llvm.<op>.with.overflow.i32`, e.g. `sadd` for `op`, and returns the result
+/// and whether it overflowed.
+pub fn with_overflow(
+    op: &str,
+    lhs: LValue,
+    rhs: LValue,
+    base: &Base,
+) -> Result<(LValue, LValue), Error> {
+    let int_ty = typ::int32(base.context());
+    let ret_ty = typ::tuple(vec![int_ty, typ::bool(base.context())], base.context());
+    let intrinsic = base.declare_function(
+        &format!("llvm.{}.with.overflow.i32", op),
+        typ::func(&mut vec![int_ty, int_ty], ret_ty),
+    )?;
+    let pair = call(intrinsic, &mut vec![lhs, rhs], base.builder());
+    unsafe {
+        let result = LLVMBuildExtractValue(base.builder(), pair, 0, b"\0".as_ptr() as *const _);
+        let overflowed = LLVMBuildExtractValue(base.builder(), pair, 1, b"\0".as_ptr() as *const _);
+        Ok((result, overflowed))
+    }
+}
+
+pub fn and(lhs: LValue, rhs: LValue, builder: LBuilder) -> LValue {
+    unsafe { LLVMBuildAnd(builder, lhs, rhs, b"\0".as_ptr() as *const _) }
+}
+
 pub fn div(lhs: LValue, rhs: LValue, builder: LBuilder) -> LValue {
     unsafe { LLVMBuildSDiv(builder, lhs, rhs, b"\0".as_ptr() as *const _) }
 }
 
+pub fn zext(value: LValue, typ: LType, builder: LBuilder) -> LValue {
+    unsafe { LLVMBuildZExt(builder, value, typ, b"\0".as_ptr() as *const _) }
+}
+
+pub fn sext(value: LValue, typ: LType, builder: LBuilder) -> LValue {
+    unsafe { LLVMBuildSExt(builder, value, typ, b"\0".as_ptr() as *const _) }
+}
+
 pub fn eq(lhs: LValue, rhs: LValue, builder: LBuilder) -> LValue {
     unsafe {
         LLVMBuildICmp(
@@ -117,6 +163,19 @@ pub fn leq(lhs: LValue, rhs: LValue, builder: LBuilder) -> LValue {
     }
 }
 
+// compares as unsigned, so that negative values are greater than any non-negative one
+pub fn ult(lhs: LValue, rhs: LValue, builder: LBuilder) -> LValue {
+    unsafe {
+        LLVMBuildICmp(
+            builder,
+            LLVMIntPredicate::LLVMIntULT,
+            lhs,
+            rhs,
+            b"\0".as_ptr() as *const _,
+        )
+    }
+}
+
 pub fn branch(block: LBasicBlock, builder: LBuilder) {
     unsafe {
         LLVMBuildBr(builder, block);
@@ -132,6 +191,12 @@ pub fn cond_branch(
     unsafe { LLVMBuildCondBr(builder, cond, then, else_) }
 }
 
+pub fn unreachable(builder: LBuilder) {
+    unsafe {
+        LLVMBuildUnreachable(builder);
+    }
+}
+
 pub fn phi(typ: LType, incoming: Vec<(LValue, LBasicBlock)>, builder: LBuilder) -> LValue {
     let len = incoming.len();
     let (mut values, mut blocks): (Vec<LValue>, Vec<LBasicBlock>) = incoming.into_iter().unzip();
@@ -161,10 +226,10 @@ pub fn call(func: LValue, args: &mut Vec<LValue>, builder: LBuilder) -> LValue {
 }
 
 pub fn gep(arr: LValue, idx: LValue, base: &Base) -> LValue {
-    let mut indices = vec![lit::int32(0, base.context), idx];
+    let mut indices = vec![lit::int32(0, base.context()), idx];
     unsafe {
         LLVMBuildGEP(
-            base.builder,
+            base.builder(),
             arr,
             indices.as_mut_ptr(),
             indices.len() as libc::c_uint,
@@ -176,7 +241,7 @@ pub fn gep(arr: LValue, idx: LValue, base: &Base) -> LValue {
 pub fn tuple_gep(e: LValue, idx: i32, base: &Base) -> LValue {
     unsafe {
         LLVMBuildStructGEP(
-            base.builder,
+            base.builder(),
             e,
             idx as libc::c_uint,
             b"\0".as_ptr() as *const _,
@@ -186,24 +251,61 @@ pub fn tuple_gep(e: LValue, idx: i32, base: &Base) -> LValue {
 
 pub mod builtin {
     use super::*;
-    pub fn print_num(value: LValue, base: &Base) -> LValue {
+    pub fn print_num(value: LValue, base: &Base) -> Result<LValue, Error> {
         unsafe {
-            let printf_name = CString::new("printf").unwrap();
-            let printf = LLVMGetNamedFunction(base.module, printf_name.as_ptr());
-
-            let format = LLVMGetNamedGlobal(
-                base.module,
-                CString::new(".builtin.format.num").unwrap().as_ptr(),
-            );
+            let printf = base.printf_function()?;
+            let format = base.num_format_str();
             let format_ptr_name = CString::new("format_ptr").unwrap();
             let format_ptr = LLVMBuildBitCast(
-                base.builder,
+                base.builder(),
                 format,
-                typ::char_ptr(base.context),
+                typ::char_ptr(base.context()),
                 format_ptr_name.as_ptr(),
             );
             let mut args = vec![format_ptr, value];
-            call(printf, &mut args, base.builder)
+            Ok(call(printf, &mut args, base.builder()))
+        }
+    }
+
+    /// Reports that `idx` is out of bounds of an array of `len` in the current function,
+    /// and aborts.
+    pub fn out_of_bounds(idx: LValue, len: LValue, base: &Base) -> Result<(), Error> {
+        let func_name = current_function_name(base);
+        let out_of_bounds = base.out_of_bounds_function()?;
+        call(out_of_bounds, &mut vec![func_name, idx, len], base.builder());
+        unreachable(base.builder());
+        Ok(())
+    }
+
+    /// Reports an arithmetic error described by `msg` in the current function, and aborts.
+    pub fn arithmetic_error(msg: &str, base: &Base) -> Result<(), Error> {
+        let func_name = current_function_name(base);
+        let msg = string_ptr(
+            &format!(".builtin.message.{}", msg.replace(' ', "_")),
+            msg,
+            base,
+        );
+        let arithmetic_error = base.arithmetic_error_function()?;
+        call(arithmetic_error, &mut vec![func_name, msg], base.builder());
+        unreachable(base.builder());
+        Ok(())
+    }
+
+    fn current_function_name(base: &Base) -> LValue {
+        let name = util::current_function_name(base.builder());
+        string_ptr(&format!(".builtin.name.{}", name), &name, base)
+    }
+
+    // a pointer to a null-terminated global string
+    fn string_ptr(global_name: &str, value: &str, base: &Base) -> LValue {
+        let global = base.global_str(global_name, &format!("{}\0", value));
+        unsafe {
+            LLVMBuildBitCast(
+                base.builder(),
+                global,
+                typ::char_ptr(base.context()),
+                b"\0".as_ptr() as *const _,
+            )
         }
     }
 }
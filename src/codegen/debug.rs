@@ -0,0 +1,346 @@
+use super::*;
+use llvm::core::*;
+use llvm::debuginfo::*;
+use llvm::prelude::{LLVMDIBuilderRef, LLVMMetadataRef};
+use std::cell::Cell;
+
+pub type LMetadata = LLVMMetadataRef;
+
+// DW_ATE_* of DWARF
+const ENCODING_BOOLEAN: LLVMDWARFTypeEncoding = 0x02;
+const ENCODING_SIGNED: LLVMDWARFTypeEncoding = 0x05;
+const ENCODING_SIGNED_CHAR: LLVMDWARFTypeEncoding = 0x06;
+
+/// DWARF debug info of a module.
+///
+/// `Expr` does not carry source locations yet, so functions, variables and instructions
+/// are all located at line 0, which DWARF reads as no line, rather than at made-up lines
+/// of the file.
+pub struct DebugInfo {
+    builder: LLVMDIBuilderRef,
+    file: LMetadata,
+    compile_unit: LMetadata,
+    // subprogram of the function being generated
+    scope: Cell<LMetadata>,
+}
+
+impl DebugInfo {
+    pub fn new(module: LModule, context: LContext, filename: &str, directory: &str) -> DebugInfo {
+        let producer = "nf";
+        unsafe {
+            let builder = LLVMCreateDIBuilder(module);
+            let file = LLVMDIBuilderCreateFile(
+                builder,
+                filename.as_ptr() as *const _,
+                filename.len(),
+                directory.as_ptr() as *const _,
+                directory.len(),
+            );
+            let compile_unit = LLVMDIBuilderCreateCompileUnit(
+                builder,
+                LLVMDWARFSourceLanguage::LLVMDWARFSourceLanguageC,
+                file,
+                producer.as_ptr() as *const _,
+                producer.len(),
+                0,
+                b"\0".as_ptr() as *const _,
+                0,
+                0,
+                b"\0".as_ptr() as *const _,
+                0,
+                LLVMDWARFEmissionKind::LLVMDWARFEmissionKindFull,
+                0,
+                0,
+                0,
+            );
+            add_module_flag(
+                module,
+                context,
+                "Debug Info Version",
+                LLVMDebugMetadataVersion(),
+            );
+            add_module_flag(module, context, "Dwarf Version", 4);
+            DebugInfo {
+                builder,
+                file,
+                compile_unit,
+                scope: Cell::new(std::ptr::null_mut()),
+            }
+        }
+    }
+
+    /// Attaches a subprogram to `func` and starts emitting locations in its scope.
+    pub fn enter_function(
+        &self,
+        func: LValue,
+        name: &str,
+        linkage_name: &str,
+        typ: &Type,
+        is_local: bool,
+        base: &Base,
+    ) -> Result<(), Error> {
+        let typ = self.subroutine_type(typ, base)?;
+        unsafe {
+            let subprogram = LLVMDIBuilderCreateFunction(
+                self.builder,
+                self.file,
+                name.as_ptr() as *const _,
+                name.len(),
+                linkage_name.as_ptr() as *const _,
+                linkage_name.len(),
+                self.file,
+                0,
+                typ,
+                is_local as llvm::prelude::LLVMBool,
+                1,
+                0,
+                LLVMDIFlagPrototyped,
+                0,
+            );
+            LLVMSetSubprogram(func, subprogram);
+            self.scope.set(subprogram);
+        }
+        // instructions must have a location in a function with debug info, if only line 0
+        self.set_location(base);
+        Ok(())
+    }
+
+    /// Stops emitting locations, so that code generated outside of functions has none.
+    pub fn leave_function(&self, base: &Base) {
+        self.scope.set(std::ptr::null_mut());
+        unsafe { LLVMSetCurrentDebugLocation2(base.builder(), std::ptr::null_mut()) }
+    }
+
+    fn set_location(&self, base: &Base) {
+        unsafe {
+            let loc = LLVMDIBuilderCreateDebugLocation(
+                base.context(),
+                0,
+                0,
+                self.scope.get(),
+                std::ptr::null_mut(),
+            );
+            LLVMSetCurrentDebugLocation2(base.builder(), loc);
+        }
+    }
+
+    /// Describes `storage`, the alloca of a `Let` binding or a param if `arg_no` is given.
+    pub fn declare_variable(
+        &self,
+        name: &str,
+        storage: LValue,
+        typ: &Type,
+        arg_no: Option<usize>,
+        base: &Base,
+    ) -> Result<(), Error> {
+        let scope = self.scope.get();
+        let typ = self.typ(typ, base)?;
+        unsafe {
+            let var = match arg_no {
+                Some(arg_no) => LLVMDIBuilderCreateParameterVariable(
+                    self.builder,
+                    scope,
+                    name.as_ptr() as *const _,
+                    name.len(),
+                    arg_no as libc::c_uint,
+                    self.file,
+                    0,
+                    typ,
+                    1,
+                    LLVMDIFlagZero,
+                ),
+                None => LLVMDIBuilderCreateAutoVariable(
+                    self.builder,
+                    scope,
+                    name.as_ptr() as *const _,
+                    name.len(),
+                    self.file,
+                    0,
+                    typ,
+                    1,
+                    LLVMDIFlagZero,
+                    0,
+                ),
+            };
+            let expr = LLVMDIBuilderCreateExpression(self.builder, std::ptr::null_mut(), 0);
+            let loc = LLVMDIBuilderCreateDebugLocation(
+                base.context(),
+                0,
+                0,
+                scope,
+                std::ptr::null_mut(),
+            );
+            LLVMDIBuilderInsertDeclareAtEnd(
+                self.builder,
+                storage,
+                var,
+                expr,
+                loc,
+                util::insertion_block(base.builder()),
+            );
+        }
+        Ok(())
+    }
+
+    /// Resolves all debug info, which must be done before the module is verified or printed.
+    pub fn finalize(&self) {
+        unsafe { LLVMDIBuilderFinalize(self.builder) }
+    }
+
+    fn subroutine_type(&self, typ: &Type, base: &Base) -> Result<LMetadata, Error> {
+        if let Type::Func(ref params, box ref ret_type, _) = typ {
+            // the first element is the return type, null for void
+            let mut types = vec![self.typ(ret_type, base)?];
+            for param in params.iter() {
+                types.push(self.typ(param, base)?);
+            }
+            Ok(unsafe {
+                LLVMDIBuilderCreateSubroutineType(
+                    self.builder,
+                    self.file,
+                    types.as_mut_ptr(),
+                    types.len() as libc::c_uint,
+                    LLVMDIFlagZero,
+                )
+            })
+        } else {
+            Err(Error::Internal(format!("not a function type: {}", typ)))
+        }
+    }
+
+    fn typ(&self, typ: &Type, base: &Base) -> Result<LMetadata, Error> {
+        let data_layout = unsafe { llvm::target::LLVMGetModuleDataLayout(base.module()) };
+        let l_typ = apply_type(typ, base)?;
+        let size_in_bits = || unsafe { llvm::target::LLVMSizeOfTypeInBits(data_layout, l_typ) };
+        let align_in_bits =
+            || unsafe { llvm::target::LLVMABIAlignmentOfType(data_layout, l_typ) * 8 };
+        match typ {
+            Type::Void => Ok(std::ptr::null_mut()),
+            Type::Bool => Ok(self.basic_type("bool", 8, ENCODING_BOOLEAN)),
+            Type::Char => Ok(self.basic_type("char", 8, ENCODING_SIGNED_CHAR)),
+            Type::Int => Ok(self.basic_type("int", 32, ENCODING_SIGNED)),
+            Type::Func(_, _, _) => self.subroutine_type(typ, base),
+            Type::Pointer(box ref ty) => {
+                let pointee = self.typ(ty, base)?;
+                Ok(unsafe {
+                    LLVMDIBuilderCreatePointerType(
+                        self.builder,
+                        pointee,
+                        size_in_bits(),
+                        align_in_bits(),
+                        0,
+                        b"\0".as_ptr() as *const _,
+                        0,
+                    )
+                })
+            }
+            Type::Array(box ref elem_ty, len) => {
+                let elem = self.typ(elem_ty, base)?;
+                unsafe {
+                    let mut subscripts = vec![LLVMDIBuilderGetOrCreateSubrange(
+                        self.builder,
+                        0,
+                        *len as i64,
+                    )];
+                    Ok(LLVMDIBuilderCreateArrayType(
+                        self.builder,
+                        size_in_bits(),
+                        align_in_bits(),
+                        elem,
+                        subscripts.as_mut_ptr(),
+                        subscripts.len() as libc::c_uint,
+                    ))
+                }
+            }
+            Type::Tuple(ref elems) => {
+                let mut members = vec![];
+                for (i, elem) in elems.iter().enumerate() {
+                    let l_elem = apply_type(elem, base)?;
+                    let member = self.typ(elem, base)?;
+                    let name = i.to_string();
+                    unsafe {
+                        members.push(LLVMDIBuilderCreateMemberType(
+                            self.builder,
+                            self.compile_unit,
+                            name.as_ptr() as *const _,
+                            name.len(),
+                            self.file,
+                            0,
+                            llvm::target::LLVMSizeOfTypeInBits(data_layout, l_elem),
+                            llvm::target::LLVMABIAlignmentOfType(data_layout, l_elem) * 8,
+                            llvm::target::LLVMOffsetOfElement(
+                                data_layout,
+                                l_typ,
+                                i as libc::c_uint,
+                            ) * 8,
+                            LLVMDIFlagZero,
+                            member,
+                        ));
+                    }
+                }
+                let name = typ.to_string();
+                Ok(unsafe {
+                    LLVMDIBuilderCreateStructType(
+                        self.builder,
+                        self.compile_unit,
+                        name.as_ptr() as *const _,
+                        name.len(),
+                        self.file,
+                        0,
+                        size_in_bits(),
+                        align_in_bits(),
+                        LLVMDIFlagZero,
+                        std::ptr::null_mut(),
+                        members.as_mut_ptr(),
+                        members.len() as libc::c_uint,
+                        0,
+                        std::ptr::null_mut(),
+                        b"\0".as_ptr() as *const _,
+                        0,
+                    )
+                })
+            }
+        }
+    }
+
+    fn basic_type(
+        &self,
+        name: &str,
+        size_in_bits: u64,
+        encoding: LLVMDWARFTypeEncoding,
+    ) -> LMetadata {
+        unsafe {
+            LLVMDIBuilderCreateBasicType(
+                self.builder,
+                name.as_ptr() as *const _,
+                name.len(),
+                size_in_bits,
+                encoding,
+                LLVMDIFlagZero,
+            )
+        }
+    }
+}
+
+impl Drop for DebugInfo {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposeDIBuilder(self.builder) }
+    }
+}
+
+fn add_module_flag(module: LModule, context: LContext, key: &str, value: libc::c_uint) {
+    unsafe {
+        let value = LLVMValueAsMetadata(LLVMConstInt(
+            LLVMInt32TypeInContext(context),
+            value as u64,
+            0,
+        ));
+        LLVMAddModuleFlag(
+            module,
+            llvm::LLVMModuleFlagBehavior::LLVMModuleFlagBehaviorWarning,
+            key.as_ptr() as *const _,
+            key.len(),
+            value,
+        );
+    }
+}
@@ -6,6 +6,12 @@ use std::io;
 pub enum Error {
     Internal(String),
     Validation(String),
+    Link(String),
+    Unsupported(String),
+    Target(String),
+    ConflictingDeclaration(String),
+    ConflictingCallConv(String),
+    Redefinition(String),
     Io(io::Error),
 }
 
@@ -14,6 +20,16 @@ impl fmt::Display for Error {
         match self {
             Error::Internal(msg) => write!(f, "internal error: {}", msg),
             Error::Validation(msg) => write!(f, "validation error: {}", msg),
+            Error::Link(msg) => write!(f, "link error: {}", msg),
+            Error::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+            Error::Target(msg) => write!(f, "target error: {}", msg),
+            Error::ConflictingDeclaration(name) => {
+                write!(f, "{} is already declared with another type", name)
+            }
+            Error::ConflictingCallConv(name) => {
+                write!(f, "{} is already declared with another calling convention", name)
+            }
+            Error::Redefinition(name) => write!(f, "{} is already defined", name),
             Error::Io(err) => write!(f, "io error: {:?}", err),
         }
     }
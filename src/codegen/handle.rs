@@ -0,0 +1,119 @@
+use super::*;
+use llvm::core::*;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::marker::PhantomData;
+
+// Owning wrappers which dispose LLVM objects when dropped.
+// Types, values and basic blocks are owned by their context, so they are left as raw `Copy`
+// handles, valid as long as the context is.
+// Modules and builders borrow their context.
+
+pub struct Context(LContext);
+
+impl Context {
+    pub fn new() -> Context {
+        Context(unsafe { LLVMContextCreate() })
+    }
+
+    pub fn as_raw(&self) -> LContext {
+        self.0
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context::new()
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe { LLVMContextDispose(self.0) }
+    }
+}
+
+/// A module which is not owned by another one yet.
+pub struct OwnedModule<'ctx>(LModule, PhantomData<&'ctx Context>);
+
+impl<'ctx> OwnedModule<'ctx> {
+    pub fn new(name: &str, context: &'ctx Context) -> OwnedModule<'ctx> {
+        let name = CString::new(name).unwrap();
+        OwnedModule(
+            unsafe { LLVMModuleCreateWithNameInContext(name.as_ptr(), context.as_raw()) },
+            PhantomData,
+        )
+    }
+
+    pub fn as_raw(&self) -> LModule {
+        self.0
+    }
+
+    /// Gives up the ownership, e.g. to the linker which destroys the module.
+    pub fn into_raw(self) -> LModule {
+        let module = self.0;
+        std::mem::forget(self);
+        module
+    }
+}
+
+impl<'ctx> Drop for OwnedModule<'ctx> {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposeModule(self.0) }
+    }
+}
+
+pub struct Builder<'ctx>(LBuilder, PhantomData<&'ctx Context>);
+
+impl<'ctx> Builder<'ctx> {
+    pub fn new(context: &'ctx Context) -> Builder<'ctx> {
+        Builder(
+            unsafe { LLVMCreateBuilderInContext(context.as_raw()) },
+            PhantomData,
+        )
+    }
+
+    pub fn as_raw(&self) -> LBuilder {
+        self.0
+    }
+}
+
+impl<'ctx> Drop for Builder<'ctx> {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposeBuilder(self.0) }
+    }
+}
+
+/// A string allocated by LLVM, which must be freed by `LLVMDisposeMessage`.
+pub struct Message(*mut libc::c_char);
+
+impl Message {
+    /// # Safety
+    /// `msg` must be a null-terminated string allocated by LLVM and owned by the caller,
+    /// or null.
+    pub unsafe fn from_raw(msg: *mut libc::c_char) -> Message {
+        Message(msg)
+    }
+
+    pub fn to_str(&self) -> std::borrow::Cow<'_, str> {
+        if self.0.is_null() {
+            "".into()
+        } else {
+            unsafe { CStr::from_ptr(self.0) }.to_string_lossy()
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl Drop for Message {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { LLVMDisposeMessage(self.0) }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use super::*;
+use crate::is_constant::Constant;
 use llvm::core::*;
-use std::ffi::CString;
 
 pub fn bool(b: bool, context: LContext) -> LValue {
     unsafe { LLVMConstInt(LLVMInt1TypeInContext(context), b as u64, 0) }
@@ -25,39 +25,48 @@ pub fn int32(n: i32, context: LContext) -> LValue {
     unsafe { LLVMConstInt(typ::int32(context), n as u64, 0) }
 }
 
-pub fn array(mut elems: Vec<LValue>, typ: LType, base: &Base) -> LValue {
-    let arr_type = typ::array(typ, elems.len());
-    unsafe {
-        if elems.iter().all(|v| LLVMIsConstant(*v) != 0) {
-            LLVMConstArray(typ, elems.as_mut_ptr(), elems.len() as u32)
-        } else {
-            let var = LLVMBuildAlloca(base.builder, arr_type, b"\0".as_ptr() as *const _);
-            for (idx, elem) in elems.into_iter().enumerate() {
-                let elem_var = build::gep(var, lit::int32(idx as i32, base.context), base);
-                build::store(elem_var, elem, base.builder);
-            }
-            build::load(var, base.builder)
+pub fn constant(c: &Constant, base: &Base) -> Result<LValue, Error> {
+    match *c {
+        Constant::Bool(b) => Ok(bool(b, base.context())),
+        Constant::Char(c) => Ok(char(c, base.context())),
+        Constant::Int(n) => Ok(int32(n, base.context())),
+        Constant::Array(ref elems, ref ty) => {
+            let typ = apply_type(ty, base)?;
+            let elems: Result<Vec<_>, _> = elems.iter().map(|c| constant(c, base)).collect();
+            let mut elems = elems?;
+            Ok(unsafe { LLVMConstArray(typ, elems.as_mut_ptr(), elems.len() as u32) })
+        }
+        Constant::Tuple(ref fields) => {
+            let fields: Result<Vec<_>, _> = fields.iter().map(|c| constant(c, base)).collect();
+            let mut fields = fields?;
+            Ok(unsafe {
+                LLVMConstStructInContext(
+                    base.context(),
+                    fields.as_mut_ptr(),
+                    fields.len() as libc::c_uint,
+                    0,
+                )
+            })
         }
     }
 }
 
-pub fn tuple(mut fields: Vec<LValue>, base: &Base) -> LValue {
-    unsafe {
-        if fields.iter().all(|v| LLVMIsConstant(*v) != 0) {
-            LLVMConstStruct(fields.as_mut_ptr(), fields.len() as libc::c_uint, 0)
-        // packed
-        } else {
-            let typ = typ::tuple(fields.iter().map(|v| type_of(*v)).collect());
-            let var = LLVMBuildAlloca(base.builder, typ, b"\0".as_ptr() as *const _);
-            for (idx, field) in fields.into_iter().enumerate() {
-                let field_var = build::gep(var, lit::int32(idx as i32, base.context), base);
-                build::store(field_var, field, base.builder);
-            }
-            build::load(var, base.builder)
-        }
+// aggregates of values which are not constant, see `constant` for the others
+pub fn array(elems: Vec<LValue>, typ: LType, base: &Base) -> LValue {
+    let arr_type = typ::array(typ, elems.len());
+    let var = unsafe { LLVMBuildAlloca(base.builder(), arr_type, b"\0".as_ptr() as *const _) };
+    for (idx, elem) in elems.into_iter().enumerate() {
+        let elem_var = build::gep(var, lit::int32(idx as i32, base.context()), base);
+        build::store(elem_var, elem, base.builder());
     }
+    build::load(var, base.builder())
 }
-pub fn external_func(name: String, typ: LType, module: LModule) -> LValue {
-    let name = CString::new(name.into_bytes()).unwrap();
-    unsafe { llvm::core::LLVMAddFunction(module, name.as_ptr(), typ) }
+
+pub fn tuple(fields: Vec<LValue>, typ: LType, base: &Base) -> LValue {
+    let var = unsafe { LLVMBuildAlloca(base.builder(), typ, b"\0".as_ptr() as *const _) };
+    for (idx, field) in fields.into_iter().enumerate() {
+        let field_var = build::gep(var, lit::int32(idx as i32, base.context()), base);
+        build::store(field_var, field, base.builder());
+    }
+    build::load(var, base.builder())
 }
@@ -0,0 +1,82 @@
+use super::*;
+use llvm::core::*;
+use llvm::transforms::pass_manager_builder::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptLevel {
+    #[default]
+    O0,
+    O1,
+    O2,
+    O3,
+}
+
+impl OptLevel {
+    fn level(self) -> libc::c_uint {
+        match self {
+            OptLevel::O0 => 0,
+            OptLevel::O1 => 1,
+            OptLevel::O2 => 2,
+            OptLevel::O3 => 3,
+        }
+    }
+
+    // the same thresholds as clang
+    fn inline_threshold(self) -> Option<libc::c_uint> {
+        match self {
+            OptLevel::O0 | OptLevel::O1 => None,
+            OptLevel::O2 => Some(225),
+            OptLevel::O3 => Some(275),
+        }
+    }
+}
+
+impl std::str::FromStr for OptLevel {
+    type Err = String;
+    /// Parses the level as given to `-O`, e.g. `2` of `-O2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "3" => Ok(OptLevel::O3),
+            _ => Err(format!("invalid optimization level: {}", s)),
+        }
+    }
+}
+
+/// Runs the standard function and module pipelines of LLVM on `module`.
+pub fn optimize(module: LModule, opt_level: OptLevel) {
+    if opt_level == OptLevel::O0 {
+        return;
+    }
+    unsafe {
+        let pmb = LLVMPassManagerBuilderCreate();
+        LLVMPassManagerBuilderSetOptLevel(pmb, opt_level.level());
+        if let Some(threshold) = opt_level.inline_threshold() {
+            LLVMPassManagerBuilderUseInlinerWithThreshold(pmb, threshold);
+        }
+
+        let fpm = LLVMCreateFunctionPassManagerForModule(module);
+        LLVMPassManagerBuilderPopulateFunctionPassManager(pmb, fpm);
+        LLVMInitializeFunctionPassManager(fpm);
+        let mut func = LLVMGetFirstFunction(module);
+        while !func.is_null() {
+            LLVMRunFunctionPassManager(fpm, func);
+            func = LLVMGetNextFunction(func);
+        }
+        LLVMFinalizeFunctionPassManager(fpm);
+        LLVMDisposePassManager(fpm);
+
+        let mpm = LLVMCreatePassManager();
+        if opt_level.inline_threshold().is_none() {
+            // so that wrappers of external functions are inlined even without the inliner
+            llvm::transforms::ipo::LLVMAddAlwaysInlinerPass(mpm);
+        }
+        LLVMPassManagerBuilderPopulateModulePassManager(pmb, mpm);
+        LLVMRunPassManager(mpm, module);
+        LLVMDisposePassManager(mpm);
+
+        LLVMPassManagerBuilderDispose(pmb);
+    }
+}
@@ -0,0 +1,92 @@
+use super::*;
+use llvm::target::*;
+use llvm::target_machine::*;
+use std::ffi::CString;
+use std::sync::Once;
+
+static INIT: Once = Once::new();
+
+/// Registers every target built into LLVM, so that any of them can be generated for.
+pub fn initialize_targets() {
+    INIT.call_once(|| unsafe {
+        LLVM_InitializeAllTargetInfos();
+        LLVM_InitializeAllTargets();
+        LLVM_InitializeAllTargetMCs();
+        LLVM_InitializeAllAsmPrinters();
+        LLVM_InitializeAllAsmParsers();
+    })
+}
+
+/// The triple of the machine LLVM was built for.
+pub fn host_triple() -> String {
+    unsafe { Message::from_raw(LLVMGetDefaultTargetTriple()) }.to_string()
+}
+
+/// A target triple with the CPU and the features to generate for.
+pub struct TargetMachine(LLVMTargetMachineRef);
+
+impl TargetMachine {
+    /// `cpu` and `features` are as given to `llc -mcpu` and `llc -mattr`, and may be empty.
+    pub fn new(
+        triple: &str,
+        cpu: &str,
+        features: &str,
+        opt_level: OptLevel,
+    ) -> Result<TargetMachine, Error> {
+        initialize_targets();
+        let triple = CString::new(triple).unwrap();
+        let cpu = CString::new(cpu).unwrap();
+        let features = CString::new(features).unwrap();
+        let level = match opt_level {
+            OptLevel::O0 => LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+            OptLevel::O1 => LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
+            OptLevel::O2 => LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+            OptLevel::O3 => LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive,
+        };
+        unsafe {
+            let mut target = std::ptr::null_mut();
+            let mut err_msg = std::ptr::null_mut();
+            if LLVMGetTargetFromTriple(triple.as_ptr(), &mut target, &mut err_msg) != 0 {
+                return Err(Error::Target(Message::from_raw(err_msg).to_string()));
+            }
+            let machine = LLVMCreateTargetMachine(
+                target,
+                triple.as_ptr(),
+                cpu.as_ptr(),
+                features.as_ptr(),
+                level,
+                LLVMRelocMode::LLVMRelocDefault,
+                LLVMCodeModel::LLVMCodeModelDefault,
+            );
+            if machine.is_null() {
+                Err(Error::Target(format!(
+                    "can not generate for {}",
+                    triple.to_string_lossy()
+                )))
+            } else {
+                Ok(TargetMachine(machine))
+            }
+        }
+    }
+
+    pub fn triple(&self) -> String {
+        unsafe { Message::from_raw(LLVMGetTargetMachineTriple(self.0)) }.to_string()
+    }
+
+    /// Sets the triple and the data layout of `module` to those of this target.
+    pub fn configure(&self, module: LModule) {
+        let triple = self.triple();
+        util::set_target(module, &triple);
+        unsafe {
+            let data_layout = LLVMCreateTargetDataLayout(self.0);
+            LLVMSetModuleDataLayout(module, data_layout);
+            LLVMDisposeTargetData(data_layout);
+        }
+    }
+}
+
+impl Drop for TargetMachine {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposeTargetMachine(self.0) }
+    }
+}
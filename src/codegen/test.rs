@@ -1,21 +1,48 @@
-use super::gen;
-use crate::{BinOp, Expr, Func, Ident, Literal, Nf, Type};
+use super::{gen, gen_with_options};
+use crate::session::Options;
+use crate::{BinOp, CallConv, Expr, Func, Ident, Literal, Nf, Type, Visibility};
 
 fn codegen_check(nf: &Nf, name: &str, expected_output: &str, expected_status: i32) {
-    use std::{fs, process::Command, str};
+    let (_, filename) = gen_file(nf, name, &Options::default());
+    lli_check(&filename, expected_output, expected_status);
+}
+
+// writes the IR of `nf` into a file named after `name`, and returns the IR and the filename
+fn gen_file(nf: &Nf, name: &str, options: &Options) -> (String, String) {
+    let nf = crate::typecheck::typing(nf).unwrap();
+    let mut ir = vec![];
+    gen_with_options(&mut ir, &nf, name, options).unwrap();
+    let ir = String::from_utf8(ir).unwrap();
     let filename = format!("./test-output/{}", name);
-    {
-        let mut f = fs::File::create(&filename).unwrap();
-        assert_eq!(gen(&mut f, &nf, name).unwrap(), ());
-    }
-    {
-        let result = Command::new("lli")
-            .arg(&filename)
-            .output()
-            .expect("failed to execute lli");
-        let output = str::from_utf8(&result.stdout).expect("unrecognized output");
-        assert_eq!(output, expected_output);
-        assert_eq!(result.status.code(), Some(expected_status));
+    std::fs::write(&filename, &ir).unwrap();
+    (ir, filename)
+}
+
+// how a program run by lli is expected to end
+enum Exit<'a> {
+    Status(i32),
+    // by a runtime check, with a message containing this on stderr
+    Abort(&'a str),
+}
+
+fn lli_check(filename: &str, expected_output: &str, expected_status: i32) {
+    lli_check_exit(filename, expected_output, Exit::Status(expected_status))
+}
+
+fn lli_check_exit(filename: &str, expected_output: &str, expected_exit: Exit) {
+    use std::{process::Command, str};
+    let result = Command::new("lli")
+        .arg(filename)
+        .output()
+        .expect("failed to execute lli");
+    let output = str::from_utf8(&result.stdout).expect("unrecognized output");
+    assert_eq!(output, expected_output);
+    match expected_exit {
+        Exit::Status(status) => assert_eq!(result.status.code(), Some(status)),
+        Exit::Abort(message) => {
+            assert!(!result.status.success());
+            assert!(String::from_utf8_lossy(&result.stderr).contains(message));
+        }
     }
 }
 
@@ -43,6 +70,7 @@ fn func_test() {
                 box Expr::Load(box Expr::Var(Ident::new("a"))),
                 box Expr::Load(box Expr::Var(Ident::new("b"))),
             ),
+            visibility: Visibility::Public,
         }],
         body: Some(Expr::Let(
             Ident::new("dummy"),
@@ -236,6 +264,7 @@ fn tuple_arg_test() {
             params: vec![(Ident::new("x"), Type::Tuple(vec![Type::Int, Type::Int]))],
             ret_type: Type::Int,
             body: Expr::Const(Literal::Int(42)),
+            visibility: Visibility::Public,
         }],
         body: Some(Expr::Call(
             box Expr::Var(Ident::new("foo")),
@@ -257,7 +286,8 @@ fn external_func_test() {
         body: Some(Expr::Call(
             box Expr::Const(Literal::ExternalFunc(
                 "abs".to_string(),
-                Type::Func(vec![Type::Int], box Type::Int),
+                Type::Func(vec![Type::Int], box Type::Int, false),
+                CallConv::C,
             )),
             vec![Expr::Const(Literal::Int(-3))],
         )),
@@ -265,3 +295,757 @@ fn external_func_test() {
     assert_eq!(crate::typecheck::check(&nf), Ok(Some(Type::Int)));
     codegen_check(&nf, "rand", "", 3); // first value when seed is 1 (default)
 }
+
+#[test]
+fn if_tuple_test() {
+    // let t: {int, int} = if true then {1, 2} else {3, 4};
+    // load t.1
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("t"),
+            Type::Tuple(vec![Type::Int, Type::Int]),
+            box Expr::If(
+                box Expr::Const(Literal::Bool(true)),
+                box Expr::Const(Literal::Tuple(vec![
+                    Expr::Const(Literal::Int(1)),
+                    Expr::Const(Literal::Int(2)),
+                ])),
+                box Expr::Const(Literal::Tuple(vec![
+                    Expr::Const(Literal::Int(3)),
+                    Expr::Const(Literal::Int(4)),
+                ])),
+            ),
+            box Expr::Load(box Expr::TupleAt(box Expr::Var(Ident::new("t")), 1)),
+        )),
+    };
+    assert_eq!(crate::typecheck::check(&nf), Ok(Some(Type::Int)));
+    codegen_check(&nf, "if-tuple", "", 2);
+}
+
+#[test]
+fn emit_into_module_test() {
+    use super::{emit, Base, Context, OwnedModule};
+    use std::ffi::CString;
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Const(Literal::Int(42))),
+    };
+    let nf = crate::typecheck::typing(&nf).unwrap();
+    let context = Context::new();
+    let module = OwnedModule::new("emit_into_module", &context);
+    let base = Base::with_module(&context, module).unwrap();
+    emit(&base, &nf).unwrap();
+    let module = base.into_module();
+    let main = CString::new("main").unwrap();
+    assert!(!unsafe { llvm::core::LLVMGetNamedFunction(module.as_raw(), main.as_ptr()) }.is_null());
+}
+
+#[test]
+fn validate_module_test() {
+    use super::{error::Error, typ, util, Base, Context};
+    let context = Context::new();
+    let base = Base::new(&context, "invalid");
+    // a function whose entry block has no terminator
+    let func = util::add_function(
+        base.module(),
+        "f",
+        typ::func(&mut vec![], typ::int32(base.context())),
+    );
+    util::add_entry_block(func, &base);
+    match util::validate_module(base.module()) {
+        Err(Error::Validation(msg)) => assert!(msg.contains("terminator")),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert!(util::print_module(base.module()).unwrap().contains("define i32 @f()"));
+}
+
+#[test]
+fn link_units_test() {
+    // unit "lib":  int twice(int x) { return (load x) + (load x); }
+    // unit "main": printnum twice(21); return 0;
+    use super::gen_units;
+    use std::fs;
+    let lib = Nf {
+        funcs: vec![Func {
+            name: Ident::new("twice"),
+            params: vec![(Ident::new("x"), Type::Int)],
+            ret_type: Type::Int,
+            body: Expr::BinOp(
+                BinOp::Add,
+                box Expr::Load(box Expr::Var(Ident::new("x"))),
+                box Expr::Load(box Expr::Var(Ident::new("x"))),
+            ),
+            visibility: Visibility::Public,
+        }],
+        body: None,
+    };
+    let main = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("dummy"),
+            Type::Void,
+            box Expr::PrintNum(box Expr::Call(
+                box Expr::Const(Literal::ExternalFunc(
+                    "twice".to_string(),
+                    Type::Func(vec![Type::Int], box Type::Int, false),
+                    CallConv::C,
+                )),
+                vec![Expr::Const(Literal::Int(21))],
+            )),
+            box Expr::Const(Literal::Int(0)),
+        )),
+    };
+    let lib = crate::typecheck::typing(&lib).unwrap();
+    let main = crate::typecheck::typing(&main).unwrap();
+    let filename = "./test-output/link-units";
+    {
+        let mut f = fs::File::create(filename).unwrap();
+        let units = [("lib", &lib), ("main", &main)];
+        gen_units(&mut f, &units, "link-units", &Options::default()).unwrap();
+    }
+    // the linked module is configured for the target as the units are
+    assert!(fs::read_to_string(filename).unwrap().contains("target datalayout"));
+    lli_check(filename, "42\n", 0);
+}
+
+#[test]
+fn link_other_context_test() {
+    use super::{error::Error, Base, Context, OwnedModule};
+    let context = Context::new();
+    let other_context = Context::new();
+    let base = Base::new(&context, "link-dest");
+    let src = Base::new(&other_context, "link-src");
+    match base.link(src) {
+        Err(Error::Link(_)) => (),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    let module = OwnedModule::new("link-foreign", &other_context);
+    match Base::with_module(&context, module) {
+        Err(Error::Link(_)) => (),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("a module in another context is accepted"),
+    }
+}
+
+#[test]
+fn import_test() {
+    // unit "lib":  public int twice(int x) { return (load x) + (load x); }
+    // unit "main": import lib; printnum twice(21); return 0;
+    use super::gen_units;
+    use crate::interface::Interface;
+    use std::fs;
+    let lib = Nf {
+        funcs: vec![Func {
+            name: Ident::new("twice"),
+            params: vec![(Ident::new("x"), Type::Int)],
+            ret_type: Type::Int,
+            body: Expr::BinOp(
+                BinOp::Add,
+                box Expr::Load(box Expr::Var(Ident::new("x"))),
+                box Expr::Load(box Expr::Var(Ident::new("x"))),
+            ),
+            visibility: Visibility::Public,
+        }],
+        body: None,
+    };
+    let main = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("dummy"),
+            Type::Void,
+            box Expr::PrintNum(box Expr::Call(
+                box Expr::Var(Ident::new("twice")),
+                vec![Expr::Const(Literal::Int(21))],
+            )),
+            box Expr::Const(Literal::Int(0)),
+        )),
+    };
+    let interface = Interface::parse(&lib.interface("lib").to_string()).unwrap();
+    let lib = crate::typecheck::typing(&lib).unwrap();
+    let main = crate::typecheck::typing_with_imports(&main, &[interface]).unwrap();
+    let filename = "./test-output/import";
+    {
+        let mut f = fs::File::create(filename).unwrap();
+        let units = [("lib", &lib), ("main", &main)];
+        gen_units(&mut f, &units, "import", &Options::default()).unwrap();
+    }
+    lli_check(filename, "42\n", 0);
+}
+
+#[test]
+fn library_test() {
+    // public int twice(int x) { return (load x) + (load x); }
+    // private int helper() { return 0; }
+    // public bool is_zero(int x) { return load x == 0; }
+    // public char pick(char c, bool b) { return if load b then load c else 'a'; }
+    use super::gen_library;
+    let nf = Nf {
+        funcs: vec![
+            Func {
+                name: Ident::new("twice"),
+                params: vec![(Ident::new("x"), Type::Int)],
+                ret_type: Type::Int,
+                body: Expr::BinOp(
+                    BinOp::Add,
+                    box Expr::Load(box Expr::Var(Ident::new("x"))),
+                    box Expr::Load(box Expr::Var(Ident::new("x"))),
+                ),
+                visibility: Visibility::Public,
+            },
+            Func {
+                name: Ident::new("helper"),
+                params: vec![],
+                ret_type: Type::Int,
+                body: Expr::Const(Literal::Int(0)),
+                visibility: Visibility::Private,
+            },
+            Func {
+                name: Ident::new("is_zero"),
+                params: vec![(Ident::new("x"), Type::Int)],
+                ret_type: Type::Bool,
+                body: Expr::BinOp(
+                    BinOp::Eq,
+                    box Expr::Load(box Expr::Var(Ident::new("x"))),
+                    box Expr::Const(Literal::Int(0)),
+                ),
+                visibility: Visibility::Public,
+            },
+            Func {
+                name: Ident::new("pick"),
+                params: vec![(Ident::new("c"), Type::Char), (Ident::new("b"), Type::Bool)],
+                ret_type: Type::Char,
+                body: Expr::If(
+                    box Expr::Load(box Expr::Var(Ident::new("b"))),
+                    box Expr::Load(box Expr::Var(Ident::new("c"))),
+                    box Expr::Const(Literal::Char('a')),
+                ),
+                visibility: Visibility::Public,
+            },
+        ],
+        body: Some(Expr::Const(Literal::Int(42))),
+    };
+    let nf = crate::typecheck::typing(&nf).unwrap();
+    let mut out = vec![];
+    gen_library(&mut out, &nf, "library").unwrap();
+    let ir = String::from_utf8(out).unwrap();
+    assert!(ir.contains("define i32 @twice(i32"));
+    assert!(ir.contains("define internal i32 @helper()"));
+    assert!(ir.contains("define zeroext i1 @is_zero(i32"));
+    // C extends `char` and `bool` to a whole register
+    assert!(ir.contains("define signext i8 @pick(i8 signext %0, i1 zeroext %1)"));
+    assert!(!ir.contains("@main"));
+    assert!(!ir.contains("printf"));
+}
+
+#[test]
+fn external_struct_return_test() {
+    // div_t div(int, int);
+    // let r: {int, int} = div(17, 5);
+    // load r.1
+    let div_t = Type::Tuple(vec![Type::Int, Type::Int]);
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("r"),
+            div_t.clone(),
+            box Expr::Call(
+                box Expr::Const(Literal::ExternalFunc(
+                    "div".to_string(),
+                    Type::Func(vec![Type::Int, Type::Int], box div_t, false),
+                    CallConv::C,
+                )),
+                vec![
+                    Expr::Const(Literal::Int(17)),
+                    Expr::Const(Literal::Int(5)),
+                ],
+            ),
+            box Expr::Load(box Expr::TupleAt(box Expr::Var(Ident::new("r")), 1)),
+        )),
+    };
+    assert_eq!(crate::typecheck::check(&nf), Ok(Some(Type::Int)));
+    codegen_check(&nf, "external-struct-return", "", 2);
+}
+
+#[test]
+fn variadic_test() {
+    // char[10] format = "%d %c %d\n";
+    // int dummy = printf(format[0], 42, 'x', true);
+    // return 0;
+    let format: Vec<Expr> = "%d %c %d\n\0"
+        .chars()
+        .map(|c| Expr::Const(Literal::Char(c)))
+        .collect();
+    let len = format.len();
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("format"),
+            Type::Array(box Type::Char, len),
+            box Expr::Const(Literal::Array(format, Type::Char)),
+            box Expr::Let(
+                Ident::new("dummy"),
+                Type::Int,
+                box Expr::Call(
+                    box Expr::Const(Literal::ExternalFunc(
+                        "printf".to_string(),
+                        Type::Func(vec![Type::Pointer(box Type::Char)], box Type::Int, true),
+                        CallConv::C,
+                    )),
+                    vec![
+                        Expr::ArrayAt(
+                            box Expr::Var(Ident::new("format")),
+                            box Expr::Const(Literal::Int(0)),
+                        ),
+                        Expr::Const(Literal::Int(42)),
+                        Expr::Const(Literal::Char('x')),
+                        Expr::Const(Literal::Bool(true)),
+                    ],
+                ),
+                box Expr::Const(Literal::Int(0)),
+            ),
+        )),
+    };
+    assert_eq!(crate::typecheck::check(&nf), Ok(Some(Type::Int)));
+    codegen_check(&nf, "variadic", "42 x 1\n", 0);
+}
+
+#[test]
+fn external_func_dedup_test() {
+    // abs(-3) + abs(-4)
+    let abs = |typ: Type| {
+        Expr::Const(Literal::ExternalFunc(
+            "abs".to_string(),
+            Type::Func(vec![Type::Int], box typ, false),
+            CallConv::C,
+        ))
+    };
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::BinOp(
+            BinOp::Add,
+            box Expr::Call(box abs(Type::Int), vec![Expr::Const(Literal::Int(-3))]),
+            box Expr::Call(box abs(Type::Int), vec![Expr::Const(Literal::Int(-4))]),
+        )),
+    };
+    codegen_check(&nf, "external-func-dedup", "", 7);
+
+    // abs(-3) + (if abs(-4) then 1 else 0)
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::BinOp(
+            BinOp::Add,
+            box Expr::Call(box abs(Type::Int), vec![Expr::Const(Literal::Int(-3))]),
+            box Expr::If(
+                box Expr::Call(box abs(Type::Bool), vec![Expr::Const(Literal::Int(-4))]),
+                box Expr::Const(Literal::Int(1)),
+                box Expr::Const(Literal::Int(0)),
+            ),
+        )),
+    };
+    let nf = crate::typecheck::typing(&nf).unwrap();
+    match gen(&mut Vec::<u8>::new(), &nf, "external-func-conflict") {
+        Err(super::Error::ConflictingDeclaration(name)) => assert_eq!(name, "abs"),
+        res => panic!("unexpected result: {:?}", res),
+    }
+
+    // abs(-3) + abs(-4), declared with different calling conventions
+    let abs_cc = |cc: CallConv| {
+        Expr::Const(Literal::ExternalFunc(
+            "abs".to_string(),
+            Type::Func(vec![Type::Int], box Type::Int, false),
+            cc,
+        ))
+    };
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::BinOp(
+            BinOp::Add,
+            box Expr::Call(box abs_cc(CallConv::C), vec![Expr::Const(Literal::Int(-3))]),
+            box Expr::Call(box abs_cc(CallConv::Cold), vec![Expr::Const(Literal::Int(-4))]),
+        )),
+    };
+    let nf = crate::typecheck::typing(&nf).unwrap();
+    match gen(&mut Vec::<u8>::new(), &nf, "external-func-conflicting-cc") {
+        Err(super::Error::ConflictingCallConv(name)) => assert_eq!(name, "abs"),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn debug_info_test() {
+    // int add(int a, int b) { let c: Int = (load a) + (load b); return load c; }
+    // printnum add(1, 2); return 0;
+    use super::{emit, error::Error, util::print_module, Base, Context};
+    use crate::session::SourceFile;
+    use std::{fs, io::Write};
+    let nf = Nf {
+        funcs: vec![Func {
+            name: Ident::new("add"),
+            params: vec![(Ident::new("a"), Type::Int), (Ident::new("b"), Type::Int)],
+            ret_type: Type::Int,
+            body: Expr::Let(
+                Ident::new("c"),
+                Type::Int,
+                box Expr::BinOp(
+                    BinOp::Add,
+                    box Expr::Load(box Expr::Var(Ident::new("a"))),
+                    box Expr::Load(box Expr::Var(Ident::new("b"))),
+                ),
+                box Expr::Load(box Expr::Var(Ident::new("c"))),
+            ),
+            visibility: Visibility::Public,
+        }],
+        body: Some(Expr::Let(
+            Ident::new("dummy"),
+            Type::Void,
+            box Expr::PrintNum(box Expr::Call(
+                box Expr::Var(Ident::new("add")),
+                vec![Expr::Const(Literal::Int(1)), Expr::Const(Literal::Int(2))],
+            )),
+            box Expr::Const(Literal::Int(0)),
+        )),
+    };
+    let nf = crate::typecheck::typing(&nf).unwrap();
+    let context = Context::new();
+    // sizes of types are unknown without a data layout
+    match Base::new(&context, "no-layout").enable_debug_info("debug-info.nf", ".") {
+        Err(Error::Target(_)) => (),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    let options = Options {
+        debug_info: Some(SourceFile {
+            filename: "debug-info.nf".to_string(),
+            directory: ".".to_string(),
+        }),
+        ..Options::default()
+    };
+    let base = Base::with_options(&context, "debug-info", &options).unwrap();
+    emit(&base, &nf).unwrap();
+    let ir = print_module(base.module()).unwrap();
+    assert!(ir.contains("!DICompileUnit("));
+    assert!(ir.contains("!DISubprogram(name: \"add\""));
+    assert!(ir.contains("!DILocalVariable(name: \"a\", arg: 1"));
+    assert!(ir.contains("!DILocalVariable(name: \"c\""));
+    // without source locations, nothing claims a line of debug-info.nf
+    assert!(ir.contains("!DILocation(line: 0,"));
+    for (i, _) in ir.match_indices("line: ") {
+        assert!(ir[i..].starts_with("line: 0,"), "{}", &ir[i..]);
+    }
+
+    let filename = "./test-output/debug-info";
+    fs::File::create(filename)
+        .unwrap()
+        .write_all(ir.as_bytes())
+        .unwrap();
+    lli_check(filename, "3\n", 0);
+}
+
+#[test]
+fn optimize_test() {
+    // same as func_test, at -O2
+    use super::{gen_optimized, OptLevel};
+    let nf = Nf {
+        funcs: vec![Func {
+            name: Ident::new("add"),
+            params: vec![(Ident::new("a"), Type::Int), (Ident::new("b"), Type::Int)],
+            ret_type: Type::Int,
+            body: Expr::BinOp(
+                BinOp::Add,
+                box Expr::Load(box Expr::Var(Ident::new("a"))),
+                box Expr::Load(box Expr::Var(Ident::new("b"))),
+            ),
+            visibility: Visibility::Public,
+        }],
+        body: Some(Expr::Let(
+            Ident::new("dummy"),
+            Type::Void,
+            box Expr::PrintNum(box Expr::Call(
+                box Expr::Var(Ident::new("add")),
+                vec![
+                    Expr::Const(Literal::Int(114)),
+                    Expr::Const(Literal::Int(514)),
+                ],
+            )),
+            box Expr::Const(Literal::Int(0)),
+        )),
+    };
+    let nf = crate::typecheck::typing(&nf).unwrap();
+    let filename = "./test-output/optimize";
+    let mut ir = vec![];
+    gen_optimized(&mut ir, &nf, "optimize", OptLevel::O2).unwrap();
+    let ir = String::from_utf8(ir).unwrap();
+    assert!(!ir.contains("alloca"));
+    std::fs::write(filename, &ir).unwrap();
+    lli_check(filename, "628\n", 0);
+
+    assert_eq!("2".parse(), Ok(OptLevel::O2));
+    assert!("fast".parse::<OptLevel>().is_err());
+}
+
+#[test]
+fn shadowing_test() {
+    // let x: Int = 1 in let x: Int = 2 in load x
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("x"),
+            Type::Int,
+            box Expr::Const(Literal::Int(1)),
+            box Expr::Let(
+                Ident::new("x"),
+                Type::Int,
+                box Expr::Const(Literal::Int(2)),
+                box Expr::Load(box Expr::Var(Ident::new("x"))),
+            ),
+        )),
+    };
+    codegen_check(&nf, "shadowing", "", 2);
+}
+
+#[test]
+fn bounds_check_test() {
+    // int[2] arr = {114, 514};
+    // int i = 0;
+    // int* dummy = (i = <idx>);
+    // return (load arr[1 - 1]) + (load arr[load i]);
+    let nf = |idx| Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("arr"),
+            Type::Array(box Type::Int, 2),
+            box Expr::Const(Literal::Array(
+                vec![
+                    Expr::Const(Literal::Int(114)),
+                    Expr::Const(Literal::Int(514)),
+                ],
+                Type::Int,
+            )),
+            // `i` is assigned, so that its value is not known at compile time
+            box Expr::Let(
+                Ident::new("i"),
+                Type::Int,
+                box Expr::Const(Literal::Int(0)),
+                box Expr::Let(
+                    Ident::new("dummy"),
+                    Type::Pointer(box Type::Int),
+                    box Expr::Assign(
+                        box Expr::Var(Ident::new("i")),
+                        box Expr::Const(Literal::Int(idx)),
+                    ),
+                    box Expr::BinOp(
+                        BinOp::Add,
+                        box Expr::Load(box Expr::ArrayAt(
+                            box Expr::Var(Ident::new("arr")),
+                            box Expr::BinOp(
+                                BinOp::Sub,
+                                box Expr::Const(Literal::Int(1)),
+                                box Expr::Const(Literal::Int(1)),
+                            ),
+                        )),
+                        box Expr::Load(box Expr::ArrayAt(
+                            box Expr::Var(Ident::new("arr")),
+                            box Expr::Load(box Expr::Var(Ident::new("i"))),
+                        )),
+                    ),
+                ),
+            ),
+        )),
+    };
+    let options = Options {
+        bounds_checks: true,
+        ..Options::default()
+    };
+
+    let (ir, filename) = gen_file(&nf(1), "bounds-check-ok", &options);
+    // only the index which is not constant is checked
+    assert_eq!(ir.matches("call void @.builtin.out_of_bounds").count(), 1);
+    lli_check(&filename, "", (114 + 514) % 256);
+
+    let (_, filename) = gen_file(&nf(-1), "bounds-check-fail", &options);
+    let expected = Exit::Abort("main: index -1 is out of bounds for length 2");
+    lli_check_exit(&filename, "", expected);
+}
+
+#[test]
+fn arithmetic_test() {
+    // int x = <lhs>;
+    // return (load x) <op> <rhs>;
+    use super::Arithmetic;
+    let nf = |lhs, op, rhs| Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("x"),
+            Type::Int,
+            box Expr::Const(Literal::Int(lhs)),
+            box Expr::BinOp(
+                op,
+                box Expr::Load(box Expr::Var(Ident::new("x"))),
+                box Expr::Const(Literal::Int(rhs)),
+            ),
+        )),
+    };
+    let gen = |nf: Nf, arithmetic, name: &str| {
+        let options = Options {
+            arithmetic,
+            ..Options::default()
+        };
+        gen_file(&nf, name, &options)
+    };
+    let abort_check = |filename: &str, message| lli_check_exit(filename, "", Exit::Abort(message));
+
+    let (ir, _) = gen(nf(1, BinOp::Add, 2), Arithmetic::NoSignedWrap, "arith-nsw");
+    assert!(ir.contains("add nsw i32"));
+
+    let (ir, filename) = gen(nf(40, BinOp::Add, 2), Arithmetic::Checked, "arith-checked");
+    assert!(ir.contains("@llvm.sadd.with.overflow.i32"));
+    lli_check(&filename, "", 42);
+
+    let (_, filename) = gen(
+        nf(i32::MAX, BinOp::Add, 1),
+        Arithmetic::Checked,
+        "arith-add-overflow",
+    );
+    abort_check(&filename, "main: overflow in addition");
+
+    let (_, filename) = gen(nf(1, BinOp::Div, 0), Arithmetic::Checked, "arith-div-zero");
+    abort_check(&filename, "main: division by zero");
+
+    let (_, filename) = gen(
+        nf(i32::MIN, BinOp::Div, -1),
+        Arithmetic::Checked,
+        "arith-div-overflow",
+    );
+    abort_check(&filename, "main: overflow in division");
+}
+
+#[test]
+fn optimized_overflow_test() {
+    // int x = 1;
+    // int dummy = 2147483647 + (load x);
+    // return 0;
+    use super::Arithmetic;
+    use crate::{ident::NameSupply, opt};
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("x"),
+            Type::Int,
+            box Expr::Const(Literal::Int(1)),
+            box Expr::Let(
+                Ident::new("dummy"),
+                Type::Int,
+                box Expr::BinOp(
+                    BinOp::Add,
+                    box Expr::Const(Literal::Int(i32::MAX)),
+                    box Expr::Load(box Expr::Var(Ident::new("x"))),
+                ),
+                box Expr::Const(Literal::Int(0)),
+            ),
+        )),
+    };
+    let wrapping = opt::optimize(nf.clone(), &Options::default(), &mut NameSupply::new());
+    assert_eq!(wrapping.body, Some(Expr::Const(Literal::Int(0))));
+
+    // the unused addition is kept, since it traps on overflow
+    let options = Options {
+        arithmetic: Arithmetic::Checked,
+        ..Options::default()
+    };
+    let nf = opt::optimize(nf, &options, &mut NameSupply::new());
+    let (_, filename) = gen_file(&nf, "optimized-overflow", &options);
+    lli_check_exit(&filename, "", Exit::Abort("main: overflow in addition"));
+}
+
+#[test]
+fn external_func_abi_test() {
+    // bool f(char, bool);
+    // if f('x', true) then 1 else 0
+    let f = Expr::Const(Literal::ExternalFunc(
+        "f".to_string(),
+        Type::Func(vec![Type::Char, Type::Bool], box Type::Bool, false),
+        CallConv::C,
+    ));
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::If(
+            box Expr::Call(
+                box f,
+                vec![
+                    Expr::Const(Literal::Char('x')),
+                    Expr::Const(Literal::Bool(true)),
+                ],
+            ),
+            box Expr::Const(Literal::Int(1)),
+            box Expr::Const(Literal::Int(0)),
+        )),
+    };
+    let nf = crate::typecheck::typing(&nf).unwrap();
+    let mut ir = vec![];
+    gen(&mut ir, &nf, "external-func-abi").unwrap();
+    let ir = String::from_utf8(ir).unwrap();
+    assert!(ir.contains("declare zeroext i1 @f(i8 signext, i1 zeroext)"));
+    assert!(ir.contains("call zeroext i1 @f(i8 signext 120, i1 zeroext true)"));
+
+    // int sum(int[2]);
+    // sum({1, 2})
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Call(
+            box Expr::Const(Literal::ExternalFunc(
+                "sum".to_string(),
+                Type::Func(vec![Type::Array(box Type::Int, 2)], box Type::Int, false),
+                CallConv::C,
+            )),
+            vec![Expr::Const(Literal::Array(
+                vec![Expr::Const(Literal::Int(1)), Expr::Const(Literal::Int(2))],
+                Type::Int,
+            ))],
+        )),
+    };
+    let nf = crate::typecheck::typing(&nf).unwrap();
+    match gen(&mut Vec::<u8>::new(), &nf, "external-func-array") {
+        Err(super::Error::Unsupported(msg)) => assert!(msg.contains("sum")),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn external_struct_other_target_test() {
+    // div_t div(int, int);
+    // let r: {int, int} = div(17, 5);
+    // load r.0
+    // on targets of which the C ABI for aggregates is not implemented
+    let div_t = Type::Tuple(vec![Type::Int, Type::Int]);
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("r"),
+            div_t.clone(),
+            box Expr::Call(
+                box Expr::Const(Literal::ExternalFunc(
+                    "div".to_string(),
+                    Type::Func(vec![Type::Int, Type::Int], box div_t, false),
+                    CallConv::C,
+                )),
+                vec![
+                    Expr::Const(Literal::Int(17)),
+                    Expr::Const(Literal::Int(5)),
+                ],
+            ),
+            box Expr::Load(box Expr::TupleAt(box Expr::Var(Ident::new("r")), 0)),
+        )),
+    };
+    let nf = crate::typecheck::typing(&nf).unwrap();
+    for triple in &["aarch64-unknown-linux-gnu", "x86_64-pc-windows-msvc"] {
+        let options = Options {
+            target: Some(triple.to_string()),
+            ..Options::default()
+        };
+        let mut out = vec![];
+        match gen_with_options(&mut out, &nf, "external-struct-other", &options) {
+            Err(super::Error::Unsupported(msg)) => assert!(msg.contains(triple)),
+            res => panic!("unexpected result for {}: {:?}", triple, res),
+        }
+    }
+}
@@ -34,8 +34,15 @@ pub fn array(typ: LType, len: usize) -> LType {
     unsafe { LLVMArrayType(typ, len as u32) }
 }
 
-pub fn tuple(mut elems: Vec<LType>) -> LType {
-    unsafe { LLVMStructType(elems.as_mut_ptr(), elems.len() as libc::c_uint, 0) }
+pub fn tuple(mut elems: Vec<LType>, context: LContext) -> LType {
+    unsafe {
+        LLVMStructTypeInContext(
+            context,
+            elems.as_mut_ptr(),
+            elems.len() as libc::c_uint,
+            0,
+        )
+    }
 }
 
 pub fn type_of(v: LValue) -> LType {
@@ -1,34 +1,49 @@
 use llvm::core::*;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 
 use super::*;
 
 pub fn validate_module(module: LModule) -> Result<(), Error> {
     use llvm::analysis::*;
-    let mut err_msg = 0 as *mut i8;
-    let buf: *mut *mut i8 = &mut err_msg;
-    let ok = unsafe {
+    let mut err_msg = std::ptr::null_mut();
+    let failed = unsafe {
         LLVMVerifyModule(
             module,
             LLVMVerifierFailureAction::LLVMReturnStatusAction,
-            buf,
+            &mut err_msg,
         )
     };
-    if ok != 0 {
-        let msg_str = unsafe { CString::from_raw(err_msg).into_string().unwrap() };
-        Err(Error::Validation(msg_str))
+    // the message is allocated even if the module is valid
+    let msg = unsafe { Message::from_raw(err_msg) };
+    if failed != 0 {
+        Err(Error::Validation(msg.to_string()))
     } else {
         Ok(())
     }
 }
 
 pub fn print_module(module: LModule) -> Result<String, Error> {
-    unsafe {
-        let ir = LLVMPrintModuleToString(module);
-        let len = libc::strlen(ir);
-        let result = String::from_raw_parts(ir as *mut u8, len + 1, len + 1);
-        Ok(result)
-    }
+    let ir = unsafe { Message::from_raw(LLVMPrintModuleToString(module)) };
+    Ok(ir.to_string())
+}
+
+pub fn set_target(module: LModule, triple: &str) {
+    let triple = CString::new(triple).unwrap();
+    unsafe { LLVMSetTarget(module, triple.as_ptr()) }
+}
+
+/// Returns the triple of `module`, which is empty unless set.
+pub fn get_target(module: LModule) -> String {
+    unsafe { CStr::from_ptr(LLVMGetTarget(module)) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Returns the data layout of `module`, which is empty unless set.
+pub fn get_data_layout(module: LModule) -> String {
+    unsafe { CStr::from_ptr(LLVMGetDataLayoutStr(module)) }
+        .to_string_lossy()
+        .into_owned()
 }
 
 pub fn add_function(module: LModule, name: &str, typ: LType) -> LValue {
@@ -36,6 +51,23 @@ pub fn add_function(module: LModule, name: &str, typ: LType) -> LValue {
     unsafe { LLVMAddFunction(module, name.as_ptr(), typ) }
 }
 
+pub fn has_body(func: LValue) -> bool {
+    unsafe { LLVMCountBasicBlocks(func) != 0 }
+}
+
+pub fn set_linkage(global: LValue, linkage: llvm::LLVMLinkage) {
+    unsafe { LLVMSetLinkage(global, linkage) }
+}
+
+pub fn link_modules(dest: LModule, src: LModule) -> Result<(), Error> {
+    let failed = unsafe { llvm::linker::LLVMLinkModules2(dest, src) };
+    if failed != 0 {
+        Err(Error::Link("failed to link modules".to_string()))
+    } else {
+        Ok(())
+    }
+}
+
 pub fn get_func_param(func: LValue, idx: usize) -> LValue {
     unsafe { LLVMGetParam(func, idx as libc::c_uint) }
 }
@@ -43,8 +75,8 @@ pub fn get_func_param(func: LValue, idx: usize) -> LValue {
 pub fn add_entry_block(func: LValue, base: &Base) -> LBasicBlock {
     unsafe {
         let block =
-            LLVMAppendBasicBlockInContext(base.context, func, b"entry\0".as_ptr() as *const _);
-        LLVMPositionBuilderAtEnd(base.builder, block);
+            LLVMAppendBasicBlockInContext(base.context(), func, b"entry\0".as_ptr() as *const _);
+        LLVMPositionBuilderAtEnd(base.builder(), block);
         block
     }
 }
@@ -59,10 +91,21 @@ pub fn insertion_block(builder: LBuilder) -> LBasicBlock {
     unsafe { LLVMGetInsertBlock(builder) }
 }
 
+/// Returns the symbol of the function in which `builder` inserts.
+pub fn current_function_name(builder: LBuilder) -> String {
+    unsafe {
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(builder));
+        let mut len = 0;
+        let name = LLVMGetValueName2(func, &mut len);
+        let bytes = std::slice::from_raw_parts(name as *const u8, len);
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
 pub fn append_block(prev_block: LBasicBlock, base: &Base) -> LBasicBlock {
     unsafe {
-        let f = LLVMGetBasicBlockParent(LLVMGetInsertBlock(base.builder));
-        let block = LLVMAppendBasicBlockInContext(base.context, f, b"\0".as_ptr() as *const _);
+        let f = LLVMGetBasicBlockParent(LLVMGetInsertBlock(base.builder()));
+        let block = LLVMAppendBasicBlockInContext(base.context(), f, b"\0".as_ptr() as *const _);
         LLVMMoveBasicBlockAfter(block, prev_block);
         block
     }
@@ -1,23 +1,160 @@
+#[cfg(test)]
+mod test;
+
 use crate::ident::Ident;
+use std::cmp::{self, Ordering};
+use std::rc::Rc;
+
+/// Immutable map from names to values.
+///
+/// It is a persistent AVL tree: `add` copies only the path to the new binding and shares
+/// the rest with `self`, so both `add` and `lookup` take O(log n) time.
+#[derive(Debug)]
+pub struct Env<T: Clone>(Option<Rc<Node<T>>>);
 
-#[derive(Debug, Clone)]
-pub struct Env<T: Clone>(Vec<(Ident, T)>);
+#[derive(Debug)]
+struct Node<T> {
+    name: Ident,
+    value: T,
+    height: usize,
+    left: Option<Rc<Node<T>>>,
+    right: Option<Rc<Node<T>>>,
+}
 
 impl<T: Clone> Env<T> {
     pub fn new() -> Self {
-        Env(vec![])
+        Env(None)
     }
 
+    /// Returns a new environment in which `name` is bound to `v`, shadowing the old binding.
     pub fn add(&self, name: Ident, v: T) -> Self {
-        let mut new_env = self.clone();
-        new_env.0.push((name, v));
-        new_env
+        Env(Some(insert(&self.0, name, v)))
     }
 
     pub fn lookup(&self, name: &Ident) -> Option<T> {
-        self.0
-            .iter()
-            .find(|e| &e.0 == name)
-            .map(|res| res.clone().1)
+        let mut node = &self.0;
+        while let Some(n) = node {
+            node = match name.cmp(&n.name) {
+                Ordering::Less => &n.left,
+                Ordering::Greater => &n.right,
+                Ordering::Equal => return Some(n.value.clone()),
+            };
+        }
+        None
+    }
+}
+
+impl<T: Clone> Clone for Env<T> {
+    fn clone(&self) -> Self {
+        Env(self.0.clone())
+    }
+}
+
+impl<T: Clone> Default for Env<T> {
+    fn default() -> Self {
+        Env::new()
+    }
+}
+
+fn height<T>(node: &Option<Rc<Node<T>>>) -> usize {
+    node.as_ref().map_or(0, |n| n.height)
+}
+
+fn make<T>(
+    name: Ident,
+    value: T,
+    left: Option<Rc<Node<T>>>,
+    right: Option<Rc<Node<T>>>,
+) -> Rc<Node<T>> {
+    Rc::new(Node {
+        name,
+        value,
+        height: cmp::max(height(&left), height(&right)) + 1,
+        left,
+        right,
+    })
+}
+
+fn insert<T: Clone>(node: &Option<Rc<Node<T>>>, name: Ident, value: T) -> Rc<Node<T>> {
+    match node {
+        None => make(name, value, None, None),
+        Some(n) => match name.cmp(&n.name) {
+            Ordering::Less => balance(
+                n.name.clone(),
+                n.value.clone(),
+                Some(insert(&n.left, name, value)),
+                n.right.clone(),
+            ),
+            Ordering::Greater => balance(
+                n.name.clone(),
+                n.value.clone(),
+                n.left.clone(),
+                Some(insert(&n.right, name, value)),
+            ),
+            Ordering::Equal => make(name, value, n.left.clone(), n.right.clone()),
+        },
+    }
+}
+
+// builds a node from subtrees whose heights differ by at most 2
+fn balance<T: Clone>(
+    name: Ident,
+    value: T,
+    left: Option<Rc<Node<T>>>,
+    right: Option<Rc<Node<T>>>,
+) -> Rc<Node<T>> {
+    let (hl, hr) = (height(&left), height(&right));
+    if hl > hr + 1 {
+        let l = left.unwrap();
+        if height(&l.left) >= height(&l.right) {
+            // single right rotation
+            make(
+                l.name.clone(),
+                l.value.clone(),
+                l.left.clone(),
+                Some(make(name, value, l.right.clone(), right)),
+            )
+        } else {
+            // double rotation
+            let lr = l.right.clone().unwrap();
+            make(
+                lr.name.clone(),
+                lr.value.clone(),
+                Some(make(
+                    l.name.clone(),
+                    l.value.clone(),
+                    l.left.clone(),
+                    lr.left.clone(),
+                )),
+                Some(make(name, value, lr.right.clone(), right)),
+            )
+        }
+    } else if hr > hl + 1 {
+        let r = right.unwrap();
+        if height(&r.right) >= height(&r.left) {
+            // single left rotation
+            make(
+                r.name.clone(),
+                r.value.clone(),
+                Some(make(name, value, left, r.left.clone())),
+                r.right.clone(),
+            )
+        } else {
+            // double rotation
+            let rl = r.left.clone().unwrap();
+            make(
+                rl.name.clone(),
+                rl.value.clone(),
+                Some(make(name, value, left, rl.left.clone())),
+                Some(make(
+                    r.name.clone(),
+                    r.value.clone(),
+                    rl.right.clone(),
+                    r.right.clone(),
+                )),
+            )
+        }
+    } else {
+        make(name, value, left, right)
     }
 }
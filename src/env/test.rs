@@ -0,0 +1,48 @@
+use super::*;
+
+fn check_balanced<T>(node: &Option<Rc<Node<T>>>) -> usize {
+    match node {
+        None => 0,
+        Some(n) => {
+            let (hl, hr) = (check_balanced(&n.left), check_balanced(&n.right));
+            assert!(hl <= hr + 1 && hr <= hl + 1);
+            assert_eq!(n.height, cmp::max(hl, hr) + 1);
+            n.height
+        }
+    }
+}
+
+#[test]
+fn lookup_test() {
+    let env = Env::new().add(Ident::new("x"), 1).add(Ident::new("y"), 2);
+    assert_eq!(env.lookup(&Ident::new("x")), Some(1));
+    assert_eq!(env.lookup(&Ident::new("y")), Some(2));
+    assert_eq!(env.lookup(&Ident::new("z")), None);
+}
+
+#[test]
+fn shadowing_test() {
+    let outer = Env::new().add(Ident::new("x"), 1);
+    let inner = outer.add(Ident::new("x"), 2);
+    assert_eq!(inner.lookup(&Ident::new("x")), Some(2));
+    // the outer environment is not affected
+    assert_eq!(outer.lookup(&Ident::new("x")), Some(1));
+}
+
+#[test]
+fn balance_test() {
+    let mut env = Env::new();
+    for i in 0..1000 {
+        env = env.add(Ident(format!("x{:04}", i)), i);
+    }
+    for i in (0..1000).rev() {
+        env = env.add(Ident(format!("y{:04}", i)), i);
+    }
+    let height = check_balanced(&env.0);
+    // an AVL tree of n nodes is at most 1.44 log2(n) high
+    assert!(height <= 16, "height: {}", height);
+    for i in 0..1000 {
+        assert_eq!(env.lookup(&Ident(format!("x{:04}", i))), Some(i));
+        assert_eq!(env.lookup(&Ident(format!("y{:04}", i))), Some(i));
+    }
+}
@@ -1,12 +1,15 @@
 use std::error;
 use std::fmt;
 
-use crate::{codegen, typecheck};
+use crate::{codegen, header, interface, resolve, typecheck};
 
 #[derive(Debug)]
 pub enum Error {
+    Resolve(resolve::error::Error),
     Typecheck(typecheck::error::Error),
     Codegen(codegen::error::Error),
+    Interface(interface::error::Error),
+    Header(header::error::Error),
     Others(String),
 }
 
@@ -14,8 +17,11 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use Error::*;
         match self {
+            Resolve(err) => write!(f, "{}", err),
             Typecheck(err) => write!(f, "{}", err),
             Codegen(err) => write!(f, "{}", err),
+            Interface(err) => write!(f, "{}", err),
+            Header(err) => write!(f, "{}", err),
             Others(msg) => write!(f, "{}", msg),
         }
     }
@@ -25,13 +31,22 @@ impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         use Error::*;
         match self {
+            Resolve(err) => Some(err),
             Typecheck(err) => Some(err),
             Codegen(err) => Some(err),
+            Interface(err) => Some(err),
+            Header(err) => Some(err),
             Others(_) => None,
         }
     }
 }
 
+impl From<resolve::error::Error> for Error {
+    fn from(err: resolve::error::Error) -> Self {
+        Error::Resolve(err)
+    }
+}
+
 impl From<typecheck::error::Error> for Error {
     fn from(err: typecheck::error::Error) -> Self {
         Error::Typecheck(err)
@@ -43,3 +58,15 @@ impl From<codegen::error::Error> for Error {
         Error::Codegen(err)
     }
 }
+
+impl From<interface::error::Error> for Error {
+    fn from(err: interface::error::Error) -> Self {
+        Error::Interface(err)
+    }
+}
+
+impl From<header::error::Error> for Error {
+    fn from(err: header::error::Error) -> Self {
+        Error::Header(err)
+    }
+}
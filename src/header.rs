@@ -0,0 +1,195 @@
+pub mod error;
+
+#[cfg(test)]
+mod test;
+
+use crate::*;
+use error::Error;
+use std::collections::HashSet;
+
+/// Writes a C header declaring the functions of `interface`.
+///
+/// Tuples and arrays become structs, and function types become typedefs of function pointers.
+/// Structs are accepted only behind pointers: nf passes tuples and arrays by value as LLVM
+/// aggregates, which is not the C calling convention.
+pub fn gen<T: std::io::Write>(out: &mut T, interface: &Interface) -> Result<(), Error> {
+    let mut header = Header::new();
+    let mut protos = vec![];
+    for decl in interface.decls.iter() {
+        if let Type::Func(_, box ref ret_ty, _) = decl.typ {
+            let ret_ty = header.c_value_type(ret_ty, true)?;
+            let params = header.c_params(&decl.typ)?;
+            protos.push(format!("{} {}({});", ret_ty, decl.symbol, params));
+        } else {
+            return Err(Error::NonFuncExport(decl.name.clone(), decl.typ.clone()));
+        }
+    }
+
+    let guard = include_guard(&interface.name);
+    writeln!(out, "#ifndef {}", guard)?;
+    writeln!(out, "#define {}", guard)?;
+    writeln!(out)?;
+    writeln!(out, "#include <stdbool.h>")?;
+    writeln!(out, "#include <stdint.h>")?;
+    writeln!(out)?;
+    for def in header.defs.iter() {
+        writeln!(out, "{}", def)?;
+    }
+    if !header.defs.is_empty() {
+        writeln!(out)?;
+    }
+    for proto in protos.iter() {
+        writeln!(out, "{}", proto)?;
+    }
+    writeln!(out)?;
+    writeln!(out, "#endif")?;
+    Ok(())
+}
+
+struct Header {
+    defs: Vec<String>,
+    defined: HashSet<String>,
+}
+
+impl Header {
+    fn new() -> Header {
+        Header {
+            defs: vec![],
+            defined: HashSet::new(),
+        }
+    }
+
+    fn c_type(&mut self, typ: &Type, allow_void: bool) -> Result<String, Error> {
+        match typ {
+            Type::Void if allow_void => Ok("void".to_string()),
+            Type::Void => Err(Error::UnsupportedType(typ.clone())),
+            Type::Bool => Ok("bool".to_string()),
+            // the signedness of plain `char` depends on the platform
+            Type::Char => Ok("signed char".to_string()),
+            Type::Int => Ok("int32_t".to_string()),
+            // function pointer typedefs are already pointers
+            Type::Pointer(box ref func @ Type::Func(_, _, _)) => self.c_type(func, false),
+            Type::Pointer(box ref typ) => Ok(format!("{}*", self.c_type(typ, true)?)),
+            Type::Func(_, box ref ret_ty, _) => {
+                let name = format!("nf_{}", mangle(typ));
+                if !self.defined.contains(&name) {
+                    let ret_ty = self.c_value_type(ret_ty, true)?;
+                    let params = self.c_params(typ)?;
+                    self.define(
+                        &name,
+                        format!("typedef {} (*{})({});", ret_ty, name, params),
+                    );
+                }
+                Ok(name)
+            }
+            // C has no empty structs or zero-length arrays
+            Type::Array(_, 0) => Err(Error::UnsupportedType(typ.clone())),
+            Type::Tuple(ref elems) if elems.is_empty() => {
+                Err(Error::UnsupportedType(typ.clone()))
+            }
+            Type::Array(box ref elem_ty, ref len) => {
+                let name = format!("nf_{}", mangle(typ));
+                if !self.defined.contains(&name) {
+                    let elem_ty = self.c_type(elem_ty, false)?;
+                    self.define(
+                        &name,
+                        format!(
+                            "typedef struct {} {{\n    {} elems[{}];\n}} {};",
+                            name, elem_ty, len, name
+                        ),
+                    );
+                }
+                Ok(name)
+            }
+            Type::Tuple(ref elems) => {
+                let name = format!("nf_{}", mangle(typ));
+                if !self.defined.contains(&name) {
+                    let mut fields = String::new();
+                    for (i, elem) in elems.iter().enumerate() {
+                        fields += &format!("    {} _{};\n", self.c_type(elem, false)?, i);
+                    }
+                    self.define(
+                        &name,
+                        format!("typedef struct {} {{\n{}}} {};", name, fields, name),
+                    );
+                }
+                Ok(name)
+            }
+        }
+    }
+
+    // type of a param or a return value
+    fn c_value_type(&mut self, typ: &Type, allow_void: bool) -> Result<String, Error> {
+        match typ {
+            Type::Array(_, _) | Type::Tuple(_) => Err(Error::AggregateByValue(typ.clone())),
+            _ => self.c_type(typ, allow_void),
+        }
+    }
+
+    fn c_params(&mut self, func_ty: &Type) -> Result<String, Error> {
+        match func_ty {
+            // C requires a named param before `...`
+            Type::Func(ref params, _, true) if params.is_empty() => {
+                Err(Error::UnsupportedType(func_ty.clone()))
+            }
+            Type::Func(ref params, _, false) if params.is_empty() => Ok("void".to_string()),
+            Type::Func(ref params, _, is_variadic) => {
+                let params: Result<Vec<_>, _> =
+                    params.iter().map(|ty| self.c_value_type(ty, false)).collect();
+                let mut params = params?;
+                if *is_variadic {
+                    params.push("...".to_string());
+                }
+                Ok(params.join(", "))
+            }
+            _ => Err(Error::UnsupportedType(func_ty.clone())),
+        }
+    }
+
+    // definitions are pushed after the ones they depend on
+    fn define(&mut self, name: &str, def: String) {
+        self.defined.insert(name.to_string());
+        self.defs.push(def);
+    }
+}
+
+// a prefix code of the type, so that different types never share a name
+fn mangle(typ: &Type) -> String {
+    match typ {
+        Type::Void => "void".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::Char => "char".to_string(),
+        Type::Int => "int".to_string(),
+        Type::Func(ref params, box ref ret_ty, is_variadic) => {
+            let prefix = if *is_variadic { "vfunc" } else { "func" };
+            let mut name = format!("{}{}", prefix, params.len());
+            for param in params.iter() {
+                name += &format!("_{}", mangle(param));
+            }
+            name + &format!("_{}", mangle(ret_ty))
+        }
+        Type::Array(box ref elem_ty, ref len) => format!("array{}_{}", len, mangle(elem_ty)),
+        Type::Pointer(box ref typ) => format!("ptr_{}", mangle(typ)),
+        Type::Tuple(ref elems) => {
+            let mut name = format!("tuple{}", elems.len());
+            for elem in elems.iter() {
+                name += &format!("_{}", mangle(elem));
+            }
+            name
+        }
+    }
+}
+
+fn include_guard(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("NF_{}_H", name)
+}
@@ -0,0 +1,42 @@
+use crate::{Ident, Type};
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    UnsupportedType(Type),
+    AggregateByValue(Type),
+    NonFuncExport(Ident, Type),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnsupportedType(ty) => write!(f, "{} can not be used in C", ty),
+            Error::AggregateByValue(ty) => {
+                write!(f, "{} can be passed to and from C only by pointer", ty)
+            }
+            Error::NonFuncExport(name, ty) => {
+                write!(f, "exported {} must have function type, but have {}", name, ty)
+            }
+            Error::Io(err) => write!(f, "io error: {:?}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,173 @@
+use super::*;
+use crate::interface::Decl;
+
+#[test]
+fn primitive_test() {
+    let interface = Interface {
+        name: "math-lib".to_string(),
+        decls: vec![
+            Decl {
+                name: Ident::new("add"),
+                symbol: "add".to_string(),
+                typ: Type::Func(vec![Type::Int, Type::Int], box Type::Int, false),
+            },
+            Decl {
+                name: Ident::new("is_zero"),
+                symbol: "math_is_zero".to_string(),
+                typ: Type::Func(
+                    vec![Type::Pointer(box Type::Char)],
+                    box Type::Bool,
+                    false,
+                ),
+            },
+            Decl {
+                name: Ident::new("reset"),
+                symbol: "reset".to_string(),
+                typ: Type::Func(vec![], box Type::Void, false),
+            },
+        ],
+    };
+    let mut out = vec![];
+    gen(&mut out, &interface).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "#ifndef NF_MATH_LIB_H
+#define NF_MATH_LIB_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+int32_t add(int32_t, int32_t);
+bool math_is_zero(signed char*);
+void reset(void);
+
+#endif
+"
+    );
+}
+
+#[test]
+fn struct_test() {
+    let pair = Type::Tuple(vec![Type::Int, Type::Char]);
+    let interface = Interface {
+        name: "lib".to_string(),
+        decls: vec![
+            Decl {
+                name: Ident::new("swap"),
+                symbol: "swap".to_string(),
+                typ: Type::Func(
+                    vec![Type::Pointer(box pair.clone())],
+                    box Type::Void,
+                    false,
+                ),
+            },
+            Decl {
+                name: Ident::new("map"),
+                symbol: "map".to_string(),
+                typ: Type::Func(
+                    vec![
+                        Type::Pointer(box Type::Func(
+                            vec![Type::Int],
+                            box Type::Int,
+                            false,
+                        )),
+                        Type::Pointer(box Type::Array(box pair, 4)),
+                    ],
+                    box Type::Void,
+                    false,
+                ),
+            },
+        ],
+    };
+    let mut out = vec![];
+    gen(&mut out, &interface).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "#ifndef NF_LIB_H
+#define NF_LIB_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+typedef struct nf_tuple2_int_char {
+    int32_t _0;
+    signed char _1;
+} nf_tuple2_int_char;
+typedef int32_t (*nf_func1_int_int)(int32_t);
+typedef struct nf_array4_tuple2_int_char {
+    nf_tuple2_int_char elems[4];
+} nf_array4_tuple2_int_char;
+
+void swap(nf_tuple2_int_char*);
+void map(nf_func1_int_int, nf_array4_tuple2_int_char*);
+
+#endif
+"
+    );
+}
+
+#[test]
+fn unsupported_test() {
+    let interface = Interface {
+        name: "lib".to_string(),
+        decls: vec![Decl {
+            name: Ident::new("f"),
+            symbol: "f".to_string(),
+            typ: Type::Func(vec![Type::Void], box Type::Int, false),
+        }],
+    };
+    let mut out = vec![];
+    match gen(&mut out, &interface) {
+        Err(Error::UnsupportedType(Type::Void)) => (),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn empty_aggregate_test() {
+    for typ in vec![Type::Tuple(vec![]), Type::Array(box Type::Int, 0)] {
+        let interface = Interface {
+            name: "lib".to_string(),
+            decls: vec![Decl {
+                name: Ident::new("f"),
+                symbol: "f".to_string(),
+                typ: Type::Func(vec![Type::Pointer(box typ.clone())], box Type::Void, false),
+            }],
+        };
+        let mut out = vec![];
+        match gen(&mut out, &interface) {
+            Err(Error::UnsupportedType(ref ty)) if ty == &typ => (),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
+
+#[test]
+fn aggregate_by_value_test() {
+    let pair = Type::Tuple(vec![Type::Int, Type::Char]);
+    let funcs = vec![
+        Type::Func(vec![pair.clone()], box Type::Void, false),
+        Type::Func(vec![], box pair.clone(), false),
+        // a callback would be called by C with the same convention
+        Type::Func(
+            vec![Type::Pointer(box Type::Func(vec![pair.clone()], box Type::Void, false))],
+            box Type::Void,
+            false,
+        ),
+    ];
+    for typ in funcs {
+        let interface = Interface {
+            name: "lib".to_string(),
+            decls: vec![Decl {
+                name: Ident::new("f"),
+                symbol: "f".to_string(),
+                typ,
+            }],
+        };
+        let mut out = vec![];
+        match gen(&mut out, &interface) {
+            Err(Error::AggregateByValue(ref ty)) if ty == &pair => (),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
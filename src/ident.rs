@@ -1,17 +1,18 @@
+#[cfg(test)]
+mod test;
+
+use crate::visit::{self, Visitor, VisitorMut};
+use crate::{Expr, Func, Nf};
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Ident(pub String);
 
 impl Ident {
     pub fn new(name: &str) -> Ident {
         Ident(name.to_string())
     }
-
-    pub fn fresh_name() -> Ident {
-        Ident(format!("fresh.{}", COUNTER.fetch_add(1, SeqCst)))
-    }
 }
 
 impl fmt::Display for Ident {
@@ -20,4 +21,100 @@ impl fmt::Display for Ident {
     }
 }
 
-static COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// Source of fresh names for one compilation.
+///
+/// Names are numbered in the order they are requested, so that the same compilation always
+/// produces the same names. Reserved names, such as the identifiers of the program being
+/// compiled, are never produced.
+#[derive(Debug, Clone, Default)]
+pub struct NameSupply {
+    next: usize,
+    reserved: HashSet<Ident>,
+    // produced names to the source names they stand for
+    origins: HashMap<Ident, Ident>,
+}
+
+impl NameSupply {
+    pub fn new() -> Self {
+        NameSupply::default()
+    }
+
+    /// Creates a supply which reserves every identifier appearing in `nf`.
+    pub fn for_nf(nf: &Nf) -> Self {
+        let mut names = NameSupply::new();
+        names.reserve_nf(nf);
+        names
+    }
+
+    pub fn reserve(&mut self, name: &Ident) {
+        self.reserved.insert(name.clone());
+    }
+
+    pub fn reserve_nf(&mut self, nf: &Nf) {
+        self.visit_nf(nf)
+    }
+
+    /// Returns a new name of the form `hint.N`, where `hint` is stripped of such a suffix.
+    pub fn fresh(&mut self, hint: &Ident) -> Ident {
+        let base = hint.0.split('.').next().unwrap_or("");
+        loop {
+            let name = Ident(format!("{}.{}", base, self.next));
+            self.next += 1;
+            if !self.reserved.contains(&name) {
+                self.reserved.insert(name.clone());
+                let origin = self.source_name(hint).clone();
+                self.origins.insert(name.clone(), origin);
+                return name;
+            }
+        }
+    }
+
+    /// The source name which `name` was produced for, through any number of renamings,
+    /// or `name` itself if it was not produced by `self`.
+    pub fn source_name<'a>(&'a self, name: &'a Ident) -> &'a Ident {
+        self.origins.get(name).unwrap_or(name)
+    }
+
+    /// Produced names to their source names, see `source_name`.
+    pub fn origins(&self) -> &HashMap<Ident, Ident> {
+        &self.origins
+    }
+
+    /// Renames the identifiers of `e` produced by `self` back to their source names,
+    /// e.g. to show `e` in a diagnostic.
+    pub fn restore_source_names(&self, e: &mut Expr) {
+        SourceNames(self).visit_expr_mut(e)
+    }
+}
+
+struct SourceNames<'a>(&'a NameSupply);
+
+impl<'a> VisitorMut for SourceNames<'a> {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        match *e {
+            Expr::Let(ref mut name, _, _, _) | Expr::Var(ref mut name) => {
+                *name = self.0.source_name(name).clone()
+            }
+            _ => (),
+        }
+        visit::walk_expr_mut(self, e)
+    }
+}
+
+impl<'a> Visitor<'a> for NameSupply {
+    fn visit_func(&mut self, func: &'a Func) {
+        self.reserve(&func.name);
+        for (param, _) in func.params.iter() {
+            self.reserve(param);
+        }
+        visit::walk_func(self, func)
+    }
+
+    fn visit_expr(&mut self, e: &'a Expr) {
+        match *e {
+            Expr::Let(ref name, _, _, _) | Expr::Var(ref name) => self.reserve(name),
+            _ => (),
+        }
+        visit::walk_expr(self, e)
+    }
+}
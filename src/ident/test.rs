@@ -0,0 +1,23 @@
+use super::*;
+
+#[test]
+fn fresh_test() {
+    let mut names = NameSupply::new();
+    assert_eq!(names.fresh(&Ident::new("x")), Ident::new("x.0"));
+    assert_eq!(names.fresh(&Ident::new("x.0")), Ident::new("x.1"));
+    assert_eq!(names.fresh(&Ident::new("y")), Ident::new("y.2"));
+
+    let mut names = NameSupply::new();
+    names.reserve(&Ident::new("x.0"));
+    assert_eq!(names.fresh(&Ident::new("x")), Ident::new("x.1"));
+}
+
+#[test]
+fn source_name_test() {
+    let mut names = NameSupply::new();
+    let x0 = names.fresh(&Ident::new("x"));
+    let x1 = names.fresh(&x0);
+    assert_eq!(names.source_name(&x0), &Ident::new("x"));
+    assert_eq!(names.source_name(&x1), &Ident::new("x"));
+    assert_eq!(names.source_name(&Ident::new("y")), &Ident::new("y"));
+}
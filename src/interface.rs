@@ -0,0 +1,202 @@
+pub mod error;
+
+#[cfg(test)]
+mod test;
+
+use crate::*;
+use error::Error;
+use std::fmt;
+
+/// Typed description of the functions a compilation unit exports,
+/// emitted alongside its IR so that other units can import them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    pub decls: Vec<Decl>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decl {
+    pub name: Ident,
+    pub symbol: String,
+    pub typ: Type,
+}
+
+impl Interface {
+    pub fn new(name: &str, nf: &Nf, symbol_prefix: &str) -> Interface {
+        let decls = nf
+            .funcs
+            .iter()
+            .filter(|func| func.visibility == Visibility::Public)
+            .map(|func| Decl {
+                name: func.name.clone(),
+                symbol: format!("{}{}", symbol_prefix, func.name),
+                typ: Type::Func(
+                    func.params.iter().map(|param| param.1.clone()).collect(),
+                    box func.ret_type.clone(),
+                    false,
+                ),
+            })
+            .collect();
+        Interface {
+            name: name.to_string(),
+            decls,
+        }
+    }
+
+    pub fn parse(src: &str) -> Result<Interface, Error> {
+        let mut lines = src
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let name = match lines.next() {
+            Some((_, line)) if line.starts_with("interface ") => {
+                line["interface ".len()..].trim().to_string()
+            }
+            Some((n, line)) => return Err(Error::Syntax(n, line.to_string())),
+            None => return Err(Error::Empty),
+        };
+
+        let mut decls = vec![];
+        for (n, line) in lines {
+            decls.push(parse_decl(line).ok_or_else(|| Error::Syntax(n, line.to_string()))?);
+        }
+        Ok(Interface { name, decls })
+    }
+}
+
+impl fmt::Display for Interface {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "interface {}", self.name)?;
+        for decl in self.decls.iter() {
+            writeln!(f, "func {} @{} : {}", decl.name, decl.symbol, decl.typ)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_decl(line: &str) -> Option<Decl> {
+    let mut words = line.splitn(4, ' ');
+    if words.next()? != "func" {
+        return None;
+    }
+    let name = Ident::new(words.next()?);
+    let symbol = words.next()?;
+    if !symbol.starts_with('@') {
+        return None;
+    }
+    let typ = words.next()?.trim();
+    if !typ.starts_with(':') {
+        return None;
+    }
+    let mut parser = TypeParser(typ[1..].trim_start());
+    let typ = parser.parse()?;
+    if !parser.0.trim().is_empty() {
+        return None;
+    }
+    if let Type::Func(_, _, _) = typ {
+        Some(Decl {
+            name,
+            symbol: symbol[1..].to_string(),
+            typ,
+        })
+    } else {
+        None
+    }
+}
+
+// parses types in the syntax printed by `Display for Type`
+struct TypeParser<'a>(&'a str);
+
+impl<'a> TypeParser<'a> {
+    fn eat(&mut self, token: &str) -> bool {
+        match self.0.trim_start().strip_prefix(token) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let rest = self.0.trim_start();
+        let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let n = rest[..len].parse().ok()?;
+        self.0 = &rest[len..];
+        Some(n)
+    }
+
+    fn list(&mut self, close: &str) -> Option<Vec<Type>> {
+        let mut types = vec![];
+        if self.eat(close) {
+            return Some(types);
+        }
+        loop {
+            types.push(self.parse()?);
+            if self.eat(close) {
+                return Some(types);
+            }
+            if !self.eat(",") {
+                return None;
+            }
+        }
+    }
+
+    fn parse(&mut self) -> Option<Type> {
+        let mut typ = if self.eat("void") {
+            Type::Void
+        } else if self.eat("bool") {
+            Type::Bool
+        } else if self.eat("char") {
+            Type::Char
+        } else if self.eat("int") {
+            Type::Int
+        } else if self.eat("pointer[") {
+            let typ = self.parse()?;
+            if !self.eat("]") {
+                return None;
+            }
+            Type::Pointer(box typ)
+        } else if self.eat("{") {
+            Type::Tuple(self.list("}")?)
+        } else if self.eat("(") {
+            let mut params = vec![];
+            let mut is_variadic = false;
+            if !self.eat(")") {
+                loop {
+                    if self.eat("...") {
+                        is_variadic = true;
+                        if !self.eat(")") {
+                            return None;
+                        }
+                        break;
+                    }
+                    params.push(self.parse()?);
+                    if self.eat(")") {
+                        break;
+                    }
+                    if !self.eat(",") {
+                        return None;
+                    }
+                }
+            }
+            if !self.eat("->") {
+                return None;
+            }
+            Type::Func(params, box self.parse()?, is_variadic)
+        } else {
+            return None;
+        };
+        while self.eat("[") {
+            let len = self.number()?;
+            if !self.eat("]") {
+                return None;
+            }
+            typ = Type::Array(box typ, len);
+        }
+        Some(typ)
+    }
+}
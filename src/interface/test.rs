@@ -0,0 +1,88 @@
+use super::*;
+
+fn lib() -> Nf {
+    // public int add(int a, int b) { return (load a) + (load b); }
+    // private {int, bool}[2] table() { ... }
+    Nf {
+        funcs: vec![
+            Func {
+                name: Ident::new("add"),
+                params: vec![(Ident::new("a"), Type::Int), (Ident::new("b"), Type::Int)],
+                ret_type: Type::Int,
+                body: Expr::BinOp(
+                    BinOp::Add,
+                    box Expr::Load(box Expr::Var(Ident::new("a"))),
+                    box Expr::Load(box Expr::Var(Ident::new("b"))),
+                ),
+                visibility: Visibility::Public,
+            },
+            Func {
+                name: Ident::new("helper"),
+                params: vec![],
+                ret_type: Type::Int,
+                body: Expr::Const(Literal::Int(0)),
+                visibility: Visibility::Private,
+            },
+        ],
+        body: None,
+    }
+}
+
+#[test]
+fn export_test() {
+    let interface = Interface::new("lib", &lib(), "lib_");
+    assert_eq!(
+        interface.decls,
+        vec![Decl {
+            name: Ident::new("add"),
+            symbol: "lib_add".to_string(),
+            typ: Type::Func(vec![Type::Int, Type::Int], box Type::Int, false),
+        }]
+    );
+    assert_eq!(
+        interface.to_string(),
+        "interface lib\nfunc add @lib_add : (int, int) -> int\n"
+    );
+}
+
+#[test]
+fn parse_test() {
+    let interface = Interface {
+        name: "lib".to_string(),
+        decls: vec![Decl {
+            name: Ident::new("f"),
+            symbol: "f".to_string(),
+            typ: Type::Func(
+                vec![
+                    Type::Pointer(box Type::Array(box Type::Char, 16)),
+                    Type::Tuple(vec![Type::Int, Type::Bool]),
+                    Type::Tuple(vec![]),
+                ],
+                box Type::Pointer(box Type::Func(vec![Type::Int], box Type::Void, false)),
+                false,
+            ),
+        }],
+    };
+    assert_eq!(Interface::parse(&interface.to_string()), Ok(interface));
+
+    assert_eq!(Interface::parse(""), Err(Error::Empty));
+    assert_eq!(
+        Interface::parse("interface lib\nfunc f @f : int\n"),
+        Err(Error::Syntax(2, "func f @f : int".to_string()))
+    );
+}
+
+#[test]
+fn variadic_test() {
+    let typ = Type::Func(vec![Type::Pointer(box Type::Char)], box Type::Int, true);
+    assert_eq!(typ.to_string(), "(pointer[char], ...) -> int");
+    let interface = Interface {
+        name: "libc".to_string(),
+        decls: vec![Decl {
+            name: Ident::new("printf"),
+            symbol: "printf".to_string(),
+            typ,
+        }],
+    };
+    assert_eq!(Interface::parse(&interface.to_string()), Ok(interface));
+}
@@ -1,4 +1,116 @@
+#[cfg(test)]
+mod test;
+
+use crate::env::Env;
 use crate::*;
 
+/// Value of an expression known at compile time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constant {
+    Bool(bool),
+    Char(char),
+    Int(i32),
+    // elements and their type
+    Array(Vec<Constant>, Type),
+    Tuple(Vec<Constant>),
+}
+
+impl Constant {
+    /// Converts a scalar literal.
+    pub fn from_literal(lit: &Literal) -> Option<Constant> {
+        match *lit {
+            Literal::Bool(b) => Some(Constant::Bool(b)),
+            Literal::Char(c) => Some(Constant::Char(c)),
+            Literal::Int(n) => Some(Constant::Int(n)),
+            _ => None,
+        }
+    }
+
+    /// Converts a scalar back to a literal.
+    pub fn into_literal(self) -> Option<Literal> {
+        match self {
+            Constant::Bool(b) => Some(Literal::Bool(b)),
+            Constant::Char(c) => Some(Literal::Char(c)),
+            Constant::Int(n) => Some(Literal::Int(n)),
+            _ => None,
+        }
+    }
+}
+
+pub fn is_constant(e: &typed::Expr) -> bool {
+    eval(e).is_some()
+}
+
+/// Evaluates `e` if it is built from scalar literals, arrays and tuples of constants,
+/// and operations on constants.
+/// Variables and functions are not constants, as their addresses are not known until runtime.
+pub fn eval(e: &typed::Expr) -> Option<Constant> {
+    use typed::ExprKind;
+    match e.kind {
+        ExprKind::Const(ref lit) => eval_literal(lit),
+        ExprKind::BinOp(ref op, box ref e1, box ref e2) => eval_binop(op, &eval(e1)?, &eval(e2)?),
+        ExprKind::If(box ref cond, box ref e1, box ref e2) => match eval(cond)? {
+            Constant::Bool(true) => eval(e1),
+            Constant::Bool(false) => eval(e2),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn eval_literal(lit: &typed::Literal) -> Option<Constant> {
+    match *lit {
+        typed::Literal::Bool(b) => Some(Constant::Bool(b)),
+        typed::Literal::Char(c) => Some(Constant::Char(c)),
+        typed::Literal::Int(n) => Some(Constant::Int(n)),
+        typed::Literal::Array(ref elems, ref ty) => {
+            let elems: Option<_> = elems.iter().map(eval).collect();
+            Some(Constant::Array(elems?, ty.clone()))
+        }
+        typed::Literal::Tuple(ref elems) => {
+            let elems: Option<_> = elems.iter().map(eval).collect();
+            Some(Constant::Tuple(elems?))
+        }
+        typed::Literal::ExternalFunc(_, _, _) => None,
+    }
+}
 
+/// Same as `eval`, but on an expression which is not typed yet, for scalars only.
+/// `consts` has the values of `Let` variables, `None` for those not known at compile time,
+/// which are read by `Load(Var(name))`.
+pub fn eval_expr(e: &Expr, consts: &Env<Option<Constant>>) -> Option<Constant> {
+    match *e {
+        Expr::Const(ref lit) => Constant::from_literal(lit),
+        Expr::Load(box Expr::Var(ref name)) => consts.lookup(name).and_then(|value| value),
+        Expr::BinOp(ref op, box ref e1, box ref e2) => {
+            eval_binop(op, &eval_expr(e1, consts)?, &eval_expr(e2, consts)?)
+        }
+        Expr::If(box ref cond, box ref e1, box ref e2) => match eval_expr(cond, consts)? {
+            Constant::Bool(true) => eval_expr(e1, consts),
+            Constant::Bool(false) => eval_expr(e2, consts),
+            _ => None,
+        },
+        _ => None,
+    }
+}
 
+/// Applies `op` to constants.
+///
+/// Overflows and divisions by zero are not evaluated, but left to runtime, where they
+/// wrap around, are undefined, or trap as `codegen::Arithmetic` says.
+pub fn eval_binop(op: &BinOp, c1: &Constant, c2: &Constant) -> Option<Constant> {
+    use Constant::*;
+    match (op, c1, c2) {
+        (BinOp::Add, Int(n1), Int(n2)) => n1.checked_add(*n2).map(Int),
+        (BinOp::Sub, Int(n1), Int(n2)) => n1.checked_sub(*n2).map(Int),
+        (BinOp::Mult, Int(n1), Int(n2)) => n1.checked_mul(*n2).map(Int),
+        (BinOp::Div, Int(n1), Int(n2)) => n1.checked_div(*n2).map(Int),
+        (BinOp::Eq, c1, c2) => Some(Bool(c1 == c2)),
+        (BinOp::Neq, c1, c2) => Some(Bool(c1 != c2)),
+        (BinOp::Lt, Int(n1), Int(n2)) => Some(Bool(n1 < n2)),
+        (BinOp::Gt, Int(n1), Int(n2)) => Some(Bool(n1 > n2)),
+        (BinOp::Leq, Int(n1), Int(n2)) => Some(Bool(n1 <= n2)),
+        (BinOp::Geq, Int(n1), Int(n2)) => Some(Bool(n1 >= n2)),
+        _ => None,
+    }
+}
@@ -0,0 +1,95 @@
+use super::*;
+
+fn typed_body(body: Expr) -> typed::Expr {
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(body),
+    };
+    crate::typecheck::typing(&nf).unwrap().body.unwrap()
+}
+
+fn int(n: i32) -> Expr {
+    Expr::Const(Literal::Int(n))
+}
+
+#[test]
+fn eval_test() {
+    // [1 + 2, 4], ('c', true)
+    let e = typed_body(Expr::Const(Literal::Tuple(vec![
+        Expr::Const(Literal::Array(
+            vec![Expr::BinOp(BinOp::Add, box int(1), box int(2)), int(4)],
+            Type::Int,
+        )),
+        Expr::Const(Literal::Tuple(vec![
+            Expr::Const(Literal::Char('c')),
+            Expr::Const(Literal::Bool(true)),
+        ])),
+    ])));
+    assert_eq!(
+        eval(&e),
+        Some(Constant::Tuple(vec![
+            Constant::Array(vec![Constant::Int(3), Constant::Int(4)], Type::Int),
+            Constant::Tuple(vec![Constant::Char('c'), Constant::Bool(true)]),
+        ]))
+    );
+
+    // if 1 < 2 then 3 else 4
+    let e = typed_body(Expr::If(
+        box Expr::BinOp(BinOp::Lt, box int(1), box int(2)),
+        box int(3),
+        box int(4),
+    ));
+    assert_eq!(eval(&e), Some(Constant::Int(3)));
+}
+
+#[test]
+fn not_constant_test() {
+    // int x = 1; [load x, 2]
+    let e = typed_body(Expr::Let(
+        Ident::new("x"),
+        Type::Int,
+        box int(1),
+        box Expr::Const(Literal::Array(
+            vec![Expr::Load(box Expr::Var(Ident::new("x"))), int(2)],
+            Type::Int,
+        )),
+    ));
+    assert!(!is_constant(&e));
+    if let typed::ExprKind::Let(_, _, _, box ref arr) = e.kind {
+        assert!(!is_constant(arr));
+    } else {
+        panic!("unexpected expr: {:?}", e);
+    }
+
+    // left to runtime, which may trap depending on the arithmetic mode
+    let e = typed_body(Expr::Const(Literal::Array(
+        vec![Expr::BinOp(BinOp::Add, box int(i32::max_value()), box int(1))],
+        Type::Int,
+    )));
+    assert!(!is_constant(&e));
+    let e = typed_body(Expr::BinOp(BinOp::Div, box int(1), box int(0)));
+    assert!(!is_constant(&e));
+}
+
+#[test]
+fn eval_expr_test() {
+    let x = Ident::new("x");
+    let y = Ident::new("y");
+    let consts = Env::new()
+        .add(x.clone(), Some(Constant::Int(2)))
+        .add(y.clone(), None);
+    let load = |name: &Ident| Expr::Load(box Expr::Var(name.clone()));
+
+    // load x * 3 - 1
+    let e = Expr::BinOp(
+        BinOp::Sub,
+        box Expr::BinOp(BinOp::Mult, box load(&x), box int(3)),
+        box int(1),
+    );
+    assert_eq!(eval_expr(&e, &consts), Some(Constant::Int(5)));
+
+    let e = Expr::BinOp(BinOp::Add, box load(&x), box load(&y));
+    assert_eq!(eval_expr(&e, &consts), None);
+    let e = Expr::BinOp(BinOp::Mult, box int(i32::min_value()), box int(-1));
+    assert_eq!(eval_expr(&e, &consts), None);
+}
@@ -4,16 +4,26 @@
 extern crate libc;
 extern crate llvm_sys as llvm;
 
-mod codegen;
+pub mod codegen;
 pub mod env;
 pub mod error;
+pub mod header;
 pub mod ident;
-mod printer;
+pub mod interface;
+pub mod is_constant;
+pub mod opt;
+pub mod printer;
+pub mod resolve;
+pub mod session;
 mod subst;
-mod typecheck;
+pub mod typecheck;
+pub mod typed;
+pub mod visit;
 
 use error::Error;
 use ident::Ident;
+use interface::Interface;
+use session::{Options, Session};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Nf {
@@ -22,10 +32,33 @@ pub struct Nf {
 }
 
 impl Nf {
+    pub fn typecheck(&self) -> Result<typed::Nf, Error> {
+        Session::default().typecheck(self)
+    }
+
+    pub fn optimize(&self) -> Nf {
+        Session::default().optimize(self)
+    }
+
+    pub fn interface(&self, name: &str) -> Interface {
+        Interface::new(name, self, "")
+    }
+
     pub fn codegen<T: std::io::Write>(&self, name: &str, out: &mut T) -> Result<(), Error> {
-        typecheck::check(self)?;
-        codegen::gen(out, self, name)?;
-        Ok(())
+        Session::default().codegen(self, name, out)
+    }
+
+    pub fn codegen_optimized<T: std::io::Write>(
+        &self,
+        name: &str,
+        opt_level: codegen::OptLevel,
+        out: &mut T,
+    ) -> Result<(), Error> {
+        let options = Options {
+            opt_level,
+            ..Options::default()
+        };
+        Session::new(options).codegen(self, name, out)
     }
 }
 
@@ -35,6 +68,13 @@ pub struct Func {
     pub params: Vec<(Ident, Type)>,
     pub ret_type: Type,
     pub body: Expr,
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Public,
+    Private,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -59,7 +99,16 @@ pub enum Literal {
     Int(i32),
     Array(Vec<Expr>, Type),
     Tuple(Vec<Expr>),
-    ExternalFunc(String, Type),
+    ExternalFunc(String, Type, CallConv),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallConv {
+    C,
+    Fast,
+    Cold,
+    X86StdCall,
+    X86FastCall,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -82,7 +131,8 @@ pub enum Type {
     Bool,
     Char,
     Int,
-    Func(Vec<Type>, Box<Type>),
+    // params, return type, and whether extra args are accepted as in C
+    Func(Vec<Type>, Box<Type>, bool),
     Array(Box<Type>, usize),
     Pointer(Box<Type>),
     Tuple(Vec<Type>),
@@ -0,0 +1,410 @@
+#[cfg(test)]
+mod test;
+
+use crate::codegen::Arithmetic;
+use crate::env::Env;
+use crate::ident::NameSupply;
+use crate::is_constant::Constant;
+use crate::session::Options;
+use crate::visit::{self, Folder, Visitor};
+use crate::*;
+
+// functions whose body has at most this many nodes are inlined
+const INLINE_SIZE_LIMIT: usize = 16;
+// the pipeline is repeated until nothing changes, at most this many times
+const MAX_ITERATIONS: usize = 8;
+
+/// Runs all passes on every function and the body of `nf` until a fixed point is reached.
+/// Runtime checks enabled by `options` are kept, even when their result is unused.
+/// Names introduced by inlining are taken from `names`.
+pub fn optimize(nf: Nf, options: &Options, names: &mut NameSupply) -> Nf {
+    names.reserve_nf(&nf);
+    let mut nf = nf;
+    for _ in 0..MAX_ITERATIONS {
+        let next = Pipeline(options).fold_nf(inline_functions(nf.clone(), names));
+        if next == nf {
+            break;
+        }
+        nf = next;
+    }
+    nf
+}
+
+// applies the passes on expressions to each whole function body
+struct Pipeline<'a>(&'a Options);
+
+impl<'a> Folder for Pipeline<'a> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        let e = fold_constants(e);
+        let e = fold_ifs(e);
+        let e = propagate_copies(e);
+        eliminate_dead_lets(e, self.0)
+    }
+}
+
+/// Evaluates `BinOp`s whose operands are literals.
+/// Overflows and divisions whose result is undefined are left to be evaluated at runtime,
+/// see `is_constant::eval_binop`.
+pub fn fold_constants(e: Expr) -> Expr {
+    ConstantFolder.fold_expr(e)
+}
+
+struct ConstantFolder;
+
+impl Folder for ConstantFolder {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        let e = visit::fold_expr(self, e);
+        if let Expr::BinOp(_, box Expr::Const(_), box Expr::Const(_)) = e {
+            let value = is_constant::eval_expr(&e, &Env::new());
+            if let Some(lit) = value.and_then(Constant::into_literal) {
+                return Expr::Const(lit);
+            }
+        }
+        e
+    }
+}
+
+/// Replaces `If`s on literal conditions with the branch taken.
+pub fn fold_ifs(e: Expr) -> Expr {
+    IfFolder.fold_expr(e)
+}
+
+struct IfFolder;
+
+impl Folder for IfFolder {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match visit::fold_expr(self, e) {
+            Expr::If(box Expr::Const(Literal::Bool(b)), box e1, box e2) => {
+                if b {
+                    e1
+                } else {
+                    e2
+                }
+            }
+            e => e,
+        }
+    }
+}
+
+/// Removes `Let`s whose variable is unused and whose initializer has no effect,
+/// including the traps of runtime checks enabled by `options`.
+pub fn eliminate_dead_lets(e: Expr, options: &Options) -> Expr {
+    DeadLetEliminator {
+        options,
+        vars: Env::new(),
+    }
+    .fold_expr(e)
+}
+
+struct DeadLetEliminator<'a> {
+    options: &'a Options,
+    // types of the `Let` variables in scope
+    vars: Env<Type>,
+}
+
+impl<'a> Folder for DeadLetEliminator<'a> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Let(name, typ, box e1, box e2) => {
+                let e1 = self.fold_expr(e1);
+                let outer = self.vars.clone();
+                self.vars = self.vars.add(name.clone(), typ.clone());
+                let e2 = self.fold_expr(e2);
+                self.vars = outer;
+                if is_pure(&e1, &self.vars, self.options) && !e2.free_vars().contains(&name) {
+                    e2
+                } else {
+                    Expr::Let(name, typ, box e1, box e2)
+                }
+            }
+            e => visit::fold_expr(self, e),
+        }
+    }
+}
+
+/// Replaces loads of a `Let` variable with its initializer, when the variable is
+/// never assigned nor referred to otherwise, and the initializer is a scalar literal
+/// or another variable.
+pub fn propagate_copies(e: Expr) -> Expr {
+    CopyPropagator.fold_expr(e)
+}
+
+struct CopyPropagator;
+
+impl Folder for CopyPropagator {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match visit::fold_expr(self, e) {
+            Expr::Let(name, typ, box e1, box e2) => {
+                let propagatable = match e1 {
+                    Expr::Const(Literal::Bool(_))
+                    | Expr::Const(Literal::Char(_))
+                    | Expr::Const(Literal::Int(_)) => true,
+                    // the variable must not be captured at the places it is propagated to
+                    Expr::Var(ref var) => var != &name && !binds(&e2, var),
+                    _ => false,
+                };
+                let e2 = if propagatable && e2.is_only_loaded(&name) {
+                    LoadReplacer {
+                        name: &name,
+                        value: &e1,
+                    }
+                    .fold_expr(e2)
+                } else {
+                    e2
+                };
+                Expr::Let(name, typ, box e1, box e2)
+            }
+            e => e,
+        }
+    }
+}
+
+// replaces free occurrences of `Load(Var(name))` with `value`
+struct LoadReplacer<'a> {
+    name: &'a Ident,
+    value: &'a Expr,
+}
+
+impl<'a> Folder for LoadReplacer<'a> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Load(box Expr::Var(ref name)) if name == self.name => self.value.clone(),
+            Expr::Let(name, typ, box e1, box e2) => {
+                let e1 = self.fold_expr(e1);
+                // `name` is shadowed in e2
+                let e2 = if &name == self.name {
+                    e2
+                } else {
+                    self.fold_expr(e2)
+                };
+                Expr::Let(name, typ, box e1, box e2)
+            }
+            e => visit::fold_expr(self, e),
+        }
+    }
+}
+
+/// Inlines calls to small functions which call no function, binding args to params by `Let`.
+pub fn inline_functions(nf: Nf, names: &mut NameSupply) -> Nf {
+    names.reserve_nf(&nf);
+    let candidates: Vec<Func> = nf
+        .funcs
+        .iter()
+        .filter(|func| is_inlinable(func))
+        .cloned()
+        .collect();
+    Inliner {
+        candidates: &candidates,
+        bound: vec![],
+        names,
+    }
+    .fold_nf(nf)
+}
+
+fn is_inlinable(func: &Func) -> bool {
+    func.params.iter().all(|(_, typ)| typ != &Type::Void)
+        && !contains_call(&func.body)
+        && size(&func.body) <= INLINE_SIZE_LIMIT
+}
+
+struct Inliner<'a> {
+    candidates: &'a [Func],
+    // names bound by `Let` around the current expression, which may shadow functions
+    bound: Vec<Ident>,
+    names: &'a mut NameSupply,
+}
+
+impl<'a> Folder for Inliner<'a> {
+    fn fold_func(&mut self, func: Func) -> Func {
+        // params shadow functions as `Let`s do
+        let len = self.bound.len();
+        self.bound.extend(func.params.iter().map(|(name, _)| name.clone()));
+        let func = visit::fold_func(self, func);
+        self.bound.truncate(len);
+        func
+    }
+
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Let(name, typ, box e1, box e2) => {
+                let e1 = self.fold_expr(e1);
+                self.bound.push(name.clone());
+                let e2 = self.fold_expr(e2);
+                self.bound.pop();
+                Expr::Let(name, typ, box e1, box e2)
+            }
+            Expr::Call(box Expr::Var(f), args) => {
+                let args: Vec<Expr> = args.into_iter().map(|arg| self.fold_expr(arg)).collect();
+                let candidate = self.candidates.iter().find(|func| func.name == f);
+                match candidate {
+                    Some(func) if !self.bound.contains(&f) && func.params.len() == args.len() => {
+                        inline_call(func, args, &self.bound, self.names)
+                            .unwrap_or_else(|args| Expr::Call(box Expr::Var(f), args))
+                    }
+                    _ => Expr::Call(box Expr::Var(f), args),
+                }
+            }
+            e => visit::fold_expr(self, e),
+        }
+    }
+}
+
+// gives the args back if the body refers to a name shadowed at the call site
+fn inline_call(
+    func: &Func,
+    args: Vec<Expr>,
+    bound: &[Ident],
+    names: &mut NameSupply,
+) -> Result<Expr, Vec<Expr>> {
+    // params are renamed so that they do not capture variables in args
+    let mut body = func.body.clone();
+    let mut params = vec![];
+    for (name, typ) in func.params.iter() {
+        let fresh = names.fresh(name);
+        body = body.subst_expr(name, &Expr::Var(fresh.clone()), names);
+        params.push((fresh, typ.clone()));
+    }
+    let fresh_params: Vec<&Ident> = params.iter().map(|(name, _)| name).collect();
+    if body
+        .free_vars()
+        .iter()
+        .any(|var| !fresh_params.contains(&var) && bound.contains(var))
+    {
+        return Err(args);
+    }
+    Ok(params
+        .into_iter()
+        .zip(args)
+        .rev()
+        .fold(body, |body, ((name, typ), arg)| {
+            Expr::Let(name, typ, box arg, box body)
+        }))
+}
+
+// `vars` are the types of the variables in scope, by which indexed arrays have known lengths
+fn is_pure(e: &Expr, vars: &Env<Type>, options: &Options) -> bool {
+    let mut purity = Purity {
+        pure: true,
+        vars: vars.clone(),
+        options,
+    };
+    purity.visit_expr(e);
+    purity.pure
+}
+
+struct Purity<'b> {
+    pure: bool,
+    vars: Env<Type>,
+    options: &'b Options,
+}
+
+impl<'b> Purity<'b> {
+    // same as `codegen::is_in_bounds`, for arrays bound to variables
+    fn is_in_bounds(&self, arr: &Expr, idx: &Expr) -> bool {
+        let len = match *arr {
+            Expr::Var(ref name) => match self.vars.lookup(name) {
+                Some(Type::Array(_, len)) => len,
+                _ => return false,
+            },
+            _ => return false,
+        };
+        match is_constant::eval_expr(idx, &Env::new()) {
+            Some(Constant::Int(n)) => n >= 0 && (n as usize) < len,
+            _ => false,
+        }
+    }
+}
+
+impl<'a, 'b> Visitor<'a> for Purity<'b> {
+    fn visit_expr(&mut self, e: &'a Expr) {
+        match *e {
+            Expr::Assign(_, _) | Expr::Call(_, _) | Expr::PrintNum(_) => self.pure = false,
+            Expr::Let(ref name, ref typ, box ref e1, box ref e2) => {
+                self.visit_expr(e1);
+                let outer = self.vars.clone();
+                self.vars = self.vars.add(name.clone(), typ.clone());
+                self.visit_expr(e2);
+                self.vars = outer;
+            }
+            // an index may be out of bounds, which aborts when checked
+            Expr::ArrayAt(box ref arr, box ref idx)
+                if self.options.bounds_checks && !self.is_in_bounds(arr, idx) =>
+            {
+                self.pure = false
+            }
+            Expr::BinOp(ref op, box ref e1, box ref e2)
+                if may_trap(op, e1, e2, self.options.arithmetic) =>
+            {
+                self.pure = false
+            }
+            _ => visit::walk_expr(self, e),
+        }
+    }
+}
+
+// whether `e1 op e2` may trap, or be undefined, at runtime
+fn may_trap(op: &BinOp, e1: &Expr, e2: &Expr, arithmetic: Arithmetic) -> bool {
+    match op {
+        // unless the divisor is known
+        BinOp::Div => match *e2 {
+            Expr::Const(Literal::Int(n)) => n == 0 || n == -1,
+            _ => true,
+        },
+        // unless the operands are known not to overflow
+        BinOp::Add | BinOp::Sub | BinOp::Mult if arithmetic == Arithmetic::Checked => {
+            let consts = Env::new();
+            match (
+                is_constant::eval_expr(e1, &consts),
+                is_constant::eval_expr(e2, &consts),
+            ) {
+                (Some(c1), Some(c2)) => is_constant::eval_binop(op, &c1, &c2).is_none(),
+                _ => true,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn contains_call(e: &Expr) -> bool {
+    struct CallFinder(bool);
+    impl<'a> Visitor<'a> for CallFinder {
+        fn visit_expr(&mut self, e: &'a Expr) {
+            match *e {
+                Expr::Call(_, _) => self.0 = true,
+                _ => visit::walk_expr(self, e),
+            }
+        }
+    }
+    let mut finder = CallFinder(false);
+    finder.visit_expr(e);
+    finder.0
+}
+
+fn size(e: &Expr) -> usize {
+    struct Counter(usize);
+    impl<'a> Visitor<'a> for Counter {
+        fn visit_expr(&mut self, e: &'a Expr) {
+            self.0 += 1;
+            visit::walk_expr(self, e)
+        }
+    }
+    let mut counter = Counter(0);
+    counter.visit_expr(e);
+    counter.0
+}
+
+// whether `e` has a `Let` binding `name`
+fn binds(e: &Expr, name: &Ident) -> bool {
+    struct BinderFinder<'b>(&'b Ident, bool);
+    impl<'a, 'b> Visitor<'a> for BinderFinder<'b> {
+        fn visit_expr(&mut self, e: &'a Expr) {
+            match *e {
+                Expr::Let(ref name, _, _, _) if name == self.0 => self.1 = true,
+                _ => visit::walk_expr(self, e),
+            }
+        }
+    }
+    let mut finder = BinderFinder(name, false);
+    finder.visit_expr(e);
+    finder.1
+}
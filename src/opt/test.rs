@@ -0,0 +1,219 @@
+use super::*;
+
+fn int(n: i32) -> Expr {
+    Expr::Const(Literal::Int(n))
+}
+
+fn load(name: &str) -> Expr {
+    Expr::Load(box Expr::Var(Ident::new(name)))
+}
+
+#[test]
+fn fold_constants_test() {
+    // (1 + 2) * 3 == 9
+    let e = Expr::BinOp(
+        BinOp::Eq,
+        box Expr::BinOp(
+            BinOp::Mult,
+            box Expr::BinOp(BinOp::Add, box int(1), box int(2)),
+            box int(3),
+        ),
+        box int(9),
+    );
+    assert_eq!(fold_constants(e), Expr::Const(Literal::Bool(true)));
+
+    // overflows and undefined divisions are kept, as they may trap at runtime
+    let e = Expr::BinOp(BinOp::Add, box int(i32::MAX), box int(1));
+    assert_eq!(fold_constants(e.clone()), e);
+    let e = Expr::BinOp(BinOp::Div, box int(1), box int(0));
+    assert_eq!(fold_constants(e.clone()), e);
+    let e = Expr::BinOp(BinOp::Div, box int(i32::MIN), box int(-1));
+    assert_eq!(fold_constants(e.clone()), e);
+}
+
+#[test]
+fn fold_ifs_test() {
+    let e = Expr::If(
+        box Expr::BinOp(BinOp::Lt, box int(1), box int(2)),
+        box int(10),
+        box int(20),
+    );
+    assert_eq!(fold_ifs(fold_constants(e)), int(10));
+}
+
+#[test]
+fn eliminate_dead_lets_test() {
+    // let x: Int = 1 + 2 in 3
+    let e = Expr::Let(
+        Ident::new("x"),
+        Type::Int,
+        box Expr::BinOp(BinOp::Add, box int(1), box int(2)),
+        box int(3),
+    );
+    assert_eq!(eliminate_dead_lets(e, &Options::default()), int(3));
+
+    // side effects are kept
+    let e = Expr::Let(
+        Ident::new("dummy"),
+        Type::Void,
+        box Expr::PrintNum(box int(1)),
+        box int(0),
+    );
+    assert_eq!(eliminate_dead_lets(e.clone(), &Options::default()), e);
+
+    // used variables are kept
+    let e = Expr::Let(Ident::new("x"), Type::Int, box int(1), box load("x"));
+    assert_eq!(eliminate_dead_lets(e.clone(), &Options::default()), e);
+}
+
+#[test]
+fn propagate_copies_test() {
+    // let x: Int = 1 in (load x) + (load x)
+    let e = Expr::Let(
+        Ident::new("x"),
+        Type::Int,
+        box int(1),
+        box Expr::BinOp(BinOp::Add, box load("x"), box load("x")),
+    );
+    assert_eq!(
+        eliminate_dead_lets(propagate_copies(e), &Options::default()),
+        Expr::BinOp(BinOp::Add, box int(1), box int(1))
+    );
+
+    // assigned variables are not propagated
+    let e = Expr::Let(
+        Ident::new("x"),
+        Type::Int,
+        box int(1),
+        box Expr::Let(
+            Ident::new("dummy"),
+            Type::Pointer(box Type::Int),
+            box Expr::Assign(box Expr::Var(Ident::new("x")), box int(2)),
+            box load("x"),
+        ),
+    );
+    assert_eq!(propagate_copies(e.clone()), e);
+}
+
+#[test]
+fn inline_functions_test() {
+    // int add(int a, int b) { return (load a) + (load b); }
+    // return add(1, 2);
+    let nf = Nf {
+        funcs: vec![Func {
+            name: Ident::new("add"),
+            params: vec![(Ident::new("a"), Type::Int), (Ident::new("b"), Type::Int)],
+            ret_type: Type::Int,
+            body: Expr::BinOp(BinOp::Add, box load("a"), box load("b")),
+            visibility: Visibility::Public,
+        }],
+        body: Some(Expr::Call(
+            box Expr::Var(Ident::new("add")),
+            vec![int(1), int(2)],
+        )),
+    };
+    let inlined = inline_functions(nf.clone(), &mut NameSupply::new());
+    assert!(crate::typecheck::check(&inlined).is_ok());
+    match inlined.body {
+        Some(Expr::Let(_, Type::Int, box ref arg, _)) => assert_eq!(arg, &int(1)),
+        ref body => panic!("not inlined: {:?}", body),
+    }
+    assert_eq!(optimize(nf, &Options::default(), &mut NameSupply::new()).body, Some(int(3)));
+}
+
+#[test]
+fn inline_shadowed_test() {
+    // int one() { return 1; }
+    // int two() { return one; }  -- refers to `one` without calling it
+    // let one: Int = 0 in two()
+    let one_ty = Type::Pointer(box Type::Func(vec![], box Type::Int, false));
+    let nf = Nf {
+        funcs: vec![
+            Func {
+                name: Ident::new("one"),
+                params: vec![],
+                ret_type: Type::Int,
+                body: int(1),
+                visibility: Visibility::Public,
+            },
+            Func {
+                name: Ident::new("two"),
+                params: vec![],
+                ret_type: one_ty,
+                body: Expr::Var(Ident::new("one")),
+                visibility: Visibility::Public,
+            },
+        ],
+        body: Some(Expr::Let(
+            Ident::new("one"),
+            Type::Int,
+            box int(0),
+            box Expr::Call(box Expr::Var(Ident::new("two")), vec![]),
+        )),
+    };
+    assert_eq!(inline_functions(nf.clone(), &mut NameSupply::new()), nf);
+}
+
+#[test]
+fn inline_shadowed_by_param_test() {
+    // int one() { return 1; }
+    // int two() { return one; }
+    // int g(int one) { return two(); }
+    let one_ty = Type::Pointer(box Type::Func(vec![], box Type::Int, false));
+    let nf = Nf {
+        funcs: vec![
+            Func {
+                name: Ident::new("one"),
+                params: vec![],
+                ret_type: Type::Int,
+                body: int(1),
+                visibility: Visibility::Public,
+            },
+            Func {
+                name: Ident::new("two"),
+                params: vec![],
+                ret_type: one_ty.clone(),
+                body: Expr::Var(Ident::new("one")),
+                visibility: Visibility::Public,
+            },
+            Func {
+                name: Ident::new("g"),
+                params: vec![(Ident::new("one"), Type::Int)],
+                ret_type: one_ty,
+                body: Expr::Call(box Expr::Var(Ident::new("two")), vec![]),
+                visibility: Visibility::Public,
+            },
+        ],
+        body: None,
+    };
+    assert_eq!(inline_functions(nf.clone(), &mut NameSupply::new()), nf);
+}
+
+#[test]
+fn eliminate_checked_indices_test() {
+    // let arr: [Int; 2] = {1, 2} in let x: Int = load arr[<idx>] in 0
+    let e = |idx| {
+        Expr::Let(
+            Ident::new("arr"),
+            Type::Array(box Type::Int, 2),
+            box Expr::Const(Literal::Array(vec![int(1), int(2)], Type::Int)),
+            box Expr::Let(
+                Ident::new("x"),
+                Type::Int,
+                box Expr::Load(box Expr::ArrayAt(
+                    box Expr::Var(Ident::new("arr")),
+                    box int(idx),
+                )),
+                box int(0),
+            ),
+        )
+    };
+    let options = Options {
+        bounds_checks: true,
+        ..Options::default()
+    };
+    assert_eq!(eliminate_dead_lets(e(2), &Options::default()), int(0));
+    assert_eq!(eliminate_dead_lets(e(1), &options), int(0));
+    // the index out of bounds is kept to abort at runtime
+    assert_eq!(eliminate_dead_lets(e(2), &options), e(2));
+}
@@ -42,11 +42,32 @@ impl fmt::Display for Literal {
                 let elems: Vec<_> = elems.iter().map(|e| e.to_string()).collect();
                 elems.join(", ")
             }),
-            ExternalFunc(ref name, ref typ) => write!(f, "external_func {} : {}", name, typ),
+            ExternalFunc(ref name, ref typ, CallConv::C) => {
+                write!(f, "external_func {} : {}", name, typ)
+            }
+            ExternalFunc(ref name, ref typ, ref cc) => {
+                write!(f, "external_func[{}] {} : {}", cc, name, typ)
+            }
         }
     }
 }
 
+impl fmt::Display for CallConv {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                CallConv::C => "ccc",
+                CallConv::Fast => "fastcc",
+                CallConv::Cold => "coldcc",
+                CallConv::X86StdCall => "x86_stdcallcc",
+                CallConv::X86FastCall => "x86_fastcallcc",
+            }
+        )
+    }
+}
+
 impl fmt::Display for BinOp {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -75,12 +96,15 @@ impl fmt::Display for Type {
             Type::Bool => write!(f, "bool"),
             Type::Char => write!(f, "char"),
             Type::Int => write!(f, "int"),
-            Type::Func(ref params, box ref ret_ty) => write!(
+            Type::Func(ref params, box ref ret_ty, is_variadic) => write!(
                 f,
                 "({}) -> {}",
                 {
-                    let params: Vec<String> =
+                    let mut params: Vec<String> =
                         params.iter().map(|param| param.to_string()).collect();
+                    if *is_variadic {
+                        params.push("...".to_string());
+                    }
                     params.join(", ")
                 },
                 ret_ty
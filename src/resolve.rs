@@ -0,0 +1,126 @@
+pub mod error;
+
+#[cfg(test)]
+mod test;
+
+use crate::ident::NameSupply;
+use crate::visit::{self, Folder};
+use crate::{env::Env, *};
+use error::Error;
+
+/// Renames every `Let` binding and param of `nf` to a unique name,
+/// so that later passes need not care about shadowing.
+/// Functions keep their names, which are their symbols.
+pub fn resolve(nf: &Nf, names: &mut NameSupply) -> Result<Nf, Error> {
+    resolve_with_imports(nf, &[], names)
+}
+
+pub fn resolve_with_imports(
+    nf: &Nf,
+    interfaces: &[Interface],
+    names: &mut NameSupply,
+) -> Result<Nf, Error> {
+    resolve_with_shadowing(nf, interfaces, names).map(|(nf, _)| nf)
+}
+
+/// Same as `resolve_with_imports`, but also returns the source names of the bindings which
+/// shadow another one in scope, in the order they appear.
+pub fn resolve_with_shadowing(
+    nf: &Nf,
+    interfaces: &[Interface],
+    names: &mut NameSupply,
+) -> Result<(Nf, Vec<Ident>), Error> {
+    names.reserve_nf(nf);
+    let mut globals = Env::new();
+    for decl in interfaces.iter().flat_map(|interface| interface.decls.iter()) {
+        names.reserve(&decl.name);
+        globals = globals.add(decl.name.clone(), decl.name.clone());
+    }
+    for (i, func) in nf.funcs.iter().enumerate() {
+        if nf.funcs[..i].iter().any(|func_| func_.name == func.name) {
+            return Err(Error::DuplicateFunction(func.name.clone()));
+        }
+        globals = globals.add(func.name.clone(), func.name.clone());
+    }
+    for func in nf.funcs.iter() {
+        for (i, (param, _)) in func.params.iter().enumerate() {
+            if func.params[..i].iter().any(|(param_, _)| param_ == param) {
+                return Err(Error::DuplicateParam(func.name.clone(), param.clone()));
+            }
+        }
+    }
+
+    let mut resolver = Resolver {
+        env: globals,
+        names,
+        shadowing: vec![],
+        error: None,
+    };
+    let nf = resolver.fold_nf(nf.clone());
+    match resolver.error {
+        Some(err) => Err(err),
+        None => Ok((nf, resolver.shadowing)),
+    }
+}
+
+struct Resolver<'a> {
+    // source names to unique names
+    env: Env<Ident>,
+    names: &'a mut NameSupply,
+    // source names of the bindings shadowing another one
+    shadowing: Vec<Ident>,
+    // the first error found, after which the result is meaningless
+    error: Option<Error>,
+}
+
+impl<'a> Resolver<'a> {
+    // binds `name` to a unique name in `self.env`, and returns the unique name
+    fn bind(&mut self, name: Ident) -> Ident {
+        if self.env.lookup(&name).is_some() {
+            self.shadowing.push(name.clone());
+        }
+        let unique_name = self.names.fresh(&name);
+        self.env = self.env.add(name, unique_name.clone());
+        unique_name
+    }
+}
+
+impl<'a> Folder for Resolver<'a> {
+    fn fold_func(&mut self, func: Func) -> Func {
+        let globals = self.env.clone();
+        let mut params = vec![];
+        for (name, typ) in func.params {
+            params.push((self.bind(name), typ));
+        }
+        let body = self.fold_expr(func.body);
+        self.env = globals;
+        Func {
+            params,
+            body,
+            ..func
+        }
+    }
+
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Let(name, typ, box e1, box e2) => {
+                let e1 = self.fold_expr(e1);
+                let outer = self.env.clone();
+                let unique_name = self.bind(name);
+                let e2 = self.fold_expr(e2);
+                self.env = outer;
+                Expr::Let(unique_name, typ, box e1, box e2)
+            }
+            Expr::Var(name) => match self.env.lookup(&name) {
+                Some(unique_name) => Expr::Var(unique_name),
+                None => {
+                    if self.error.is_none() {
+                        self.error = Some(Error::UnboundVariable(name.clone()));
+                    }
+                    Expr::Var(name)
+                }
+            },
+            e => visit::fold_expr(self, e),
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use std::error;
+use std::fmt;
+
+use crate::ident::Ident;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    DuplicateFunction(Ident),
+    DuplicateParam(Ident, Ident),
+    UnboundVariable(Ident),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match self {
+            DuplicateFunction(name) => write!(f, "function {} is defined more than once", name),
+            DuplicateParam(func, param) => {
+                write!(f, "param {} of function {} is declared more than once", param, func)
+            }
+            UnboundVariable(name) => write!(f, "unbound variable: {}", name),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
@@ -0,0 +1,114 @@
+use super::*;
+
+fn func(name: &str, params: &[&str], body: Expr) -> Func {
+    Func {
+        name: Ident::new(name),
+        params: params
+            .iter()
+            .map(|param| (Ident::new(param), Type::Int))
+            .collect(),
+        ret_type: Type::Int,
+        body,
+        visibility: Visibility::Public,
+    }
+}
+
+fn load(name: &str) -> Expr {
+    Expr::Load(box Expr::Var(Ident::new(name)))
+}
+
+#[test]
+fn shadowing_test() {
+    // let x: Int = 1 in let x: Int = (load x) + 1 in load x
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("x"),
+            Type::Int,
+            box Expr::Const(Literal::Int(1)),
+            box Expr::Let(
+                Ident::new("x"),
+                Type::Int,
+                box Expr::BinOp(BinOp::Add, box load("x"), box Expr::Const(Literal::Int(1))),
+                box load("x"),
+            ),
+        )),
+    };
+    let (resolved, shadowing) = resolve_with_shadowing(&nf, &[], &mut NameSupply::new()).unwrap();
+    assert_eq!(shadowing, vec![Ident::new("x")]);
+    match resolved.body {
+        Some(Expr::Let(
+            ref outer,
+            _,
+            _,
+            box Expr::Let(ref inner, _, box Expr::BinOp(_, box ref e1, _), box ref e2),
+        )) => {
+            assert_ne!(outer, inner);
+            assert_eq!(e1, &Expr::Load(box Expr::Var(outer.clone())));
+            assert_eq!(e2, &Expr::Load(box Expr::Var(inner.clone())));
+        }
+        ref body => panic!("unexpected body: {:?}", body),
+    }
+    assert!(resolved.body.unwrap().alpha_eq(nf.body.as_ref().unwrap()));
+}
+
+#[test]
+fn params_test() {
+    // int f(int a) { return load a; }
+    // int g(int a) { return f(load a); }
+    let nf = Nf {
+        funcs: vec![
+            func("f", &["a"], load("a")),
+            func(
+                "g",
+                &["a"],
+                Expr::Call(box Expr::Var(Ident::new("f")), vec![load("a")]),
+            ),
+        ],
+        body: None,
+    };
+    let resolved = resolve(&nf, &mut NameSupply::new()).unwrap();
+    let (f, g) = (&resolved.funcs[0], &resolved.funcs[1]);
+    assert_eq!(f.name, Ident::new("f"));
+    assert_ne!(f.params[0].0, g.params[0].0);
+    assert_eq!(
+        g.body,
+        Expr::Call(
+            box Expr::Var(Ident::new("f")),
+            vec![Expr::Load(box Expr::Var(g.params[0].0.clone()))]
+        )
+    );
+}
+
+#[test]
+fn error_test() {
+    let nf = Nf {
+        funcs: vec![
+            func("f", &[], Expr::Const(Literal::Int(0))),
+            func("f", &[], Expr::Const(Literal::Int(1))),
+        ],
+        body: None,
+    };
+    assert_eq!(
+        resolve(&nf, &mut NameSupply::new()),
+        Err(Error::DuplicateFunction(Ident::new("f")))
+    );
+
+    let nf = Nf {
+        funcs: vec![func("f", &["a", "a"], load("a"))],
+        body: None,
+    };
+    assert_eq!(
+        resolve(&nf, &mut NameSupply::new()),
+        Err(Error::DuplicateParam(Ident::new("f"), Ident::new("a")))
+    );
+
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(load("x")),
+    };
+    assert_eq!(
+        resolve(&nf, &mut NameSupply::new()),
+        Err(Error::UnboundVariable(Ident::new("x")))
+    );
+}
@@ -0,0 +1,279 @@
+#[cfg(test)]
+mod test;
+
+use crate::codegen::{Arithmetic, Module, OptLevel, OutputKind};
+use crate::error::Error;
+use crate::ident::NameSupply;
+use crate::*;
+use std::cmp;
+use std::fmt;
+use std::panic;
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Configuration of a compilation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// Target triple of the generated module, the host's if `None`.
+    pub target: Option<String>,
+    /// CPU to generate for, as given to `llc -mcpu`; the generic one of the target if empty.
+    pub cpu: String,
+    /// Features to enable or disable, as given to `llc -mattr`, e.g. `+sse4.2,-avx`.
+    pub features: String,
+    pub opt_level: OptLevel,
+    pub output: OutputKind,
+    /// Source file described by DWARF debug info, which is emitted only if given.
+    pub debug_info: Option<SourceFile>,
+    /// Whether an out-of-bounds index of an array aborts the program, instead of being
+    /// undefined behavior. Constant indices known to be in bounds are not checked.
+    pub bounds_checks: bool,
+    pub arithmetic: Arithmetic,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            target: None,
+            cpu: String::new(),
+            features: String::new(),
+            opt_level: OptLevel::O0,
+            output: OutputKind::Executable,
+            debug_info: None,
+            bounds_checks: false,
+            arithmetic: Arithmetic::Wrapping,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    pub filename: String,
+    pub directory: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.level {
+            Level::Warning => write!(f, "warning: {}", self.message),
+            Level::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+/// State shared by the passes of a compilation: its options, the supply of fresh names,
+/// and the diagnostics reported so far.
+///
+/// Errors are returned as usual, and also recorded as diagnostics so that a driver can
+/// print every diagnostic in order.
+#[derive(Debug, Default)]
+pub struct Session {
+    pub options: Options,
+    pub names: NameSupply,
+    /// Interfaces of the units imported by the programs compiled in this session.
+    pub imports: Vec<Interface>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Session {
+    pub fn new(options: Options) -> Session {
+        Session {
+            options,
+            names: NameSupply::new(),
+            imports: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    pub fn add_import(&mut self, interface: Interface) {
+        self.imports.push(interface);
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn warn(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic {
+            level: Level::Warning,
+            message,
+        });
+    }
+
+    fn report<T>(&mut self, result: Result<T, Error>) -> Result<T, Error> {
+        if let Err(ref err) = result {
+            self.diagnostics.push(Diagnostic {
+                level: Level::Error,
+                message: err.to_string(),
+            });
+        }
+        result
+    }
+
+    pub fn typecheck(&mut self, nf: &Nf) -> Result<typed::Nf, Error> {
+        let result = self.typecheck_unreported(nf);
+        self.report(result)
+    }
+
+    fn typecheck_unreported(&mut self, nf: &Nf) -> Result<typed::Nf, Error> {
+        let (nf, shadowing) =
+            resolve::resolve_with_shadowing(nf, &self.imports, &mut self.names)?;
+        for name in shadowing {
+            self.warn(format!("{} shadows another binding of the same name", name));
+        }
+        self.typing(&nf)
+    }
+
+    // type checks `nf` once it is resolved
+    fn typing(&mut self, nf: &Nf) -> Result<typed::Nf, Error> {
+        let result = typecheck::typing_with_imports(nf, &self.imports);
+        Ok(result.map_err(|err| err.with_source_names(&self.names))?)
+    }
+
+    pub fn optimize(&mut self, nf: &Nf) -> Nf {
+        opt::optimize(nf.clone(), &self.options, &mut self.names)
+    }
+
+    /// Type checks `nf` and generates it into a module named `name`, as configured by `options`.
+    pub fn codegen<T: std::io::Write>(
+        &mut self,
+        nf: &Nf,
+        name: &str,
+        out: &mut T,
+    ) -> Result<(), Error> {
+        let module = self.codegen_module(nf, name)?;
+        let result = module.print().and_then(|ir| Ok(write!(out, "{}", ir)?));
+        self.report(result.map_err(Error::from))
+    }
+
+    /// Same as `codegen`, but returns the module instead of printing it.
+    /// Unless `opt_level` is `O0`, `nf` is optimized by `opt` before it is generated.
+    pub fn codegen_module(&mut self, nf: &Nf, name: &str) -> Result<Module, Error> {
+        if self.options.output == OutputKind::Library && nf.body.is_some() {
+            self.warn(format!("the body of {} is ignored in a library", name));
+        }
+        let mut typed_nf = self.typecheck(nf)?;
+        if self.options.opt_level != OptLevel::O0 {
+            // checked as written first, so that diagnostics are about the source program
+            let optimized = self.optimize(nf);
+            let result = resolve::resolve_with_imports(&optimized, &self.imports, &mut self.names)
+                .map_err(Error::from)
+                .and_then(|nf| self.typing(&nf));
+            typed_nf = self.report(result)?;
+        }
+        let nf = typed_nf;
+        let result = codegen::gen_module_with_names(&nf, name, &self.options, &self.names);
+        self.report(result.map_err(Error::from))
+    }
+
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+/// Result of compiling one unit of a batch.
+pub struct Output {
+    pub name: String,
+    pub module: Result<Module, Error>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Compiles each of `units`, given as pairs of a module name and a program, on up to
+/// `threads` threads.
+///
+/// Each unit is compiled in a session and an LLVM context of its own, so the outputs do not
+/// depend on the number of threads nor on the order in which the units are picked up.
+/// They are returned in the order of `units`.
+pub fn compile_batch(units: Vec<(String, Nf)>, options: &Options, threads: usize) -> Vec<Output> {
+    let len = units.len();
+    let units = Arc::new(units);
+    let options = Arc::new(options.clone());
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+    let workers: Vec<_> = (0..cmp::max(1, cmp::min(threads, len)))
+        .map(|_| {
+            let units = units.clone();
+            let options = options.clone();
+            let next = next.clone();
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                let i = next.fetch_add(1, atomic::Ordering::SeqCst);
+                if i >= units.len() {
+                    break;
+                }
+                let (ref name, ref nf) = units[i];
+                let output = compile_unit(name, nf, &options);
+                if sender.send((i, output)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let mut outputs: Vec<Option<Output>> = (0..len).map(|_| None).collect();
+    for (i, output) in receiver.iter() {
+        outputs[i] = Some(output);
+    }
+    // panics are caught per unit, but a unit lost anyway is reported as failed
+    for worker in workers {
+        let _ = worker.join();
+    }
+    outputs
+        .into_iter()
+        .zip(units.iter())
+        .map(|(output, (name, _))| {
+            output.unwrap_or_else(|| internal_error(name, "the unit was not compiled"))
+        })
+        .collect()
+}
+
+// a panic in one unit is reported as its error instead of taking down the whole batch
+fn compile_unit(name: &str, nf: &Nf, options: &Options) -> Output {
+    let result = panic::catch_unwind(|| {
+        let mut session = Session::new(options.clone());
+        let module = session.codegen_module(nf, name);
+        (module, session.into_diagnostics())
+    });
+    match result {
+        Ok((module, diagnostics)) => Output {
+            name: name.to_string(),
+            module,
+            diagnostics,
+        },
+        Err(payload) => {
+            let cause = match payload.downcast_ref::<&str>() {
+                Some(cause) => cause.to_string(),
+                None => match payload.downcast_ref::<String>() {
+                    Some(cause) => cause.clone(),
+                    None => "unknown panic".to_string(),
+                },
+            };
+            internal_error(name, &cause)
+        }
+    }
+}
+
+fn internal_error(name: &str, cause: &str) -> Output {
+    let message = format!("internal error while compiling {}: {}", name, cause);
+    Output {
+        name: name.to_string(),
+        module: Err(Error::Others(message.clone())),
+        diagnostics: vec![Diagnostic {
+            level: Level::Error,
+            message,
+        }],
+    }
+}
@@ -0,0 +1,230 @@
+use super::*;
+
+fn answer() -> Nf {
+    Nf {
+        funcs: vec![],
+        body: Some(Expr::Const(Literal::Int(42))),
+    }
+}
+
+#[test]
+fn codegen_test() {
+    let options = Options {
+        target: Some("x86_64-unknown-linux-gnu".to_string()),
+        ..Options::default()
+    };
+    let mut session = Session::new(options);
+    let mut ir = vec![];
+    session.codegen(&answer(), "session", &mut ir).unwrap();
+    let ir = String::from_utf8(ir).unwrap();
+    assert!(ir.contains("target triple = \"x86_64-unknown-linux-gnu\""));
+    assert!(ir.contains("target datalayout = \"e-m:e-"));
+    assert!(ir.contains("define i32 @main()"));
+    assert!(session.diagnostics().is_empty());
+}
+
+#[test]
+fn diagnostics_test() {
+    let options = Options {
+        output: OutputKind::Library,
+        ..Options::default()
+    };
+    let mut session = Session::new(options);
+    let mut ir = vec![];
+    session.codegen(&answer(), "library", &mut ir).unwrap();
+    assert_eq!(session.diagnostics().len(), 1);
+    assert_eq!(session.diagnostics()[0].level, Level::Warning);
+
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Load(box Expr::Var(Ident::new("x")))),
+    };
+    let mut session = Session::default();
+    assert!(session.typecheck(&nf).is_err());
+    assert_eq!(
+        session.diagnostics(),
+        &[Diagnostic {
+            level: Level::Error,
+            message: "unbound variable: x".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn source_names_test() {
+    // let x: Int = 1 in load x
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("x"),
+            Type::Int,
+            box Expr::Const(Literal::Int(1)),
+            box Expr::Load(box Expr::Var(Ident::new("x"))),
+        )),
+    };
+    let mut session = Session::default();
+    let mut ir = vec![];
+    session.codegen(&nf, "source-names", &mut ir).unwrap();
+    let ir = String::from_utf8(ir).unwrap();
+    assert!(ir.contains("%x = alloca i32"));
+    assert!(!ir.contains("%x.0"));
+}
+
+#[test]
+fn shadowing_test() {
+    // let x: Int = 1 in let x: Bool = load x in load x
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("x"),
+            Type::Int,
+            box Expr::Const(Literal::Int(1)),
+            box Expr::Let(
+                Ident::new("x"),
+                Type::Bool,
+                box Expr::Load(box Expr::Var(Ident::new("x"))),
+                box Expr::Load(box Expr::Var(Ident::new("x"))),
+            ),
+        )),
+    };
+    let mut session = Session::default();
+    assert!(session.typecheck(&nf).is_err());
+    let diagnostics = session.diagnostics();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].level, Level::Warning);
+    assert!(diagnostics[0].message.contains("x shadows"));
+    // the type error shows the source names, not the ones given by `resolve`
+    assert_eq!(diagnostics[1].level, Level::Error);
+    assert!(diagnostics[1].message.contains("load (x)"));
+    assert!(!diagnostics[1].message.contains("x."));
+}
+
+#[test]
+fn optimize_test() {
+    // let x: Int = 20 in let x: Int = (load x) + 22 in load x, at O2
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("x"),
+            Type::Int,
+            box Expr::Const(Literal::Int(20)),
+            box Expr::Let(
+                Ident::new("x"),
+                Type::Int,
+                box Expr::BinOp(
+                    BinOp::Add,
+                    box Expr::Load(box Expr::Var(Ident::new("x"))),
+                    box Expr::Const(Literal::Int(22)),
+                ),
+                box Expr::Load(box Expr::Var(Ident::new("x"))),
+            ),
+        )),
+    };
+    let options = Options {
+        opt_level: OptLevel::O2,
+        ..Options::default()
+    };
+    let mut session = Session::new(options);
+    let mut ir = vec![];
+    session.codegen(&nf, "optimize", &mut ir).unwrap();
+    let ir = String::from_utf8(ir).unwrap();
+    assert!(ir.contains("ret i32 42"));
+    // the optimized program is not diagnosed again
+    assert_eq!(session.diagnostics().len(), 1);
+    assert_eq!(session.diagnostics()[0].level, Level::Warning);
+}
+
+#[test]
+fn imports_test() {
+    // twice(21), where `twice` is exported by another unit
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Call(
+            box Expr::Var(Ident::new("twice")),
+            vec![Expr::Const(Literal::Int(21))],
+        )),
+    };
+    let mut session = Session::default();
+    assert!(session.typecheck(&nf).is_err());
+
+    let mut session = Session::default();
+    session.add_import(Interface {
+        name: "lib".to_string(),
+        decls: vec![crate::interface::Decl {
+            name: Ident::new("twice"),
+            symbol: "lib_twice".to_string(),
+            typ: Type::Func(vec![Type::Int], box Type::Int, false),
+        }],
+    });
+    let mut ir = vec![];
+    session.codegen(&nf, "imports", &mut ir).unwrap();
+    let ir = String::from_utf8(ir).unwrap();
+    assert!(ir.contains("declare i32 @lib_twice(i32)"));
+    assert!(session.diagnostics().is_empty());
+}
+
+#[test]
+fn target_test() {
+    let options = Options {
+        target: Some("aarch64-unknown-linux-gnu".to_string()),
+        cpu: "cortex-a53".to_string(),
+        features: "+neon".to_string(),
+        ..Options::default()
+    };
+    let mut ir = vec![];
+    Session::new(options)
+        .codegen(&answer(), "cross", &mut ir)
+        .unwrap();
+    let ir = String::from_utf8(ir).unwrap();
+    assert!(ir.contains("target triple = \"aarch64-unknown-linux-gnu\""));
+
+    let options = Options {
+        target: Some("nowhere-unknown-none".to_string()),
+        ..Options::default()
+    };
+    let mut session = Session::new(options);
+    assert!(session.codegen(&answer(), "unknown", &mut vec![]).is_err());
+    assert_eq!(session.diagnostics()[0].level, Level::Error);
+}
+
+#[test]
+fn compile_batch_test() {
+    let units: Vec<(String, Nf)> = (0..16)
+        .map(|i| {
+            let nf = Nf {
+                funcs: vec![],
+                body: Some(Expr::Let(
+                    Ident::new("x"),
+                    Type::Int,
+                    box Expr::Const(Literal::Int(i)),
+                    box Expr::Load(box Expr::Var(Ident::new(if i == 7 { "y" } else { "x" }))),
+                )),
+            };
+            (format!("unit{}", i), nf)
+        })
+        .collect();
+    let print = |outputs: Vec<Output>| -> Vec<Result<String, ()>> {
+        outputs
+            .into_iter()
+            .map(|output| output.module.map(|module| module.print().unwrap()).map_err(|_| ()))
+            .collect()
+    };
+    let sequential = print(compile_batch(units.clone(), &Options::default(), 1));
+    let parallel = print(compile_batch(units, &Options::default(), 4));
+    assert_eq!(sequential, parallel);
+    assert!(sequential[7].is_err());
+    assert!(sequential[3].as_ref().unwrap().contains("unit3"));
+}
+
+#[test]
+fn compile_batch_panic_test() {
+    // module names can not contain NUL, on which the compilation of the unit panics
+    let units = vec![
+        ("bad\0unit".to_string(), answer()),
+        ("good".to_string(), answer()),
+    ];
+    let outputs = compile_batch(units, &Options::default(), 2);
+    assert!(outputs[0].module.is_err());
+    assert_eq!(outputs[0].diagnostics[0].level, Level::Error);
+    assert!(outputs[1].module.is_ok());
+}
@@ -1,57 +1,179 @@
+#[cfg(test)]
+mod test;
+
 use crate::*;
-use ident::Ident;
+use env::Env;
+use std::collections::HashSet;
+use ident::{Ident, NameSupply};
+use visit::{Folder, Visitor};
 
 impl Func {
-    pub fn subst_expr(mut self: Func, name: &Ident, e: &Expr) -> Func {
-        self.body = self.body.subst_expr(name, e);
+    pub fn subst_expr(mut self: Func, name: &Ident, e: &Expr, names: &mut NameSupply) -> Func {
+        self.body = self.body.subst_expr(name, e, names);
         self
     }
 }
 
 impl Expr {
-    pub fn subst_expr(self: Expr, name: &Ident, e: &Expr) -> Expr {
-        match self {
-            Expr::Const(Literal::Bool(_))
-            | Expr::Const(Literal::Char(_))
-            | Expr::Const(Literal::Int(_)) => self,
-            Expr::Const(Literal::Array(es, typ)) => Expr::Const(Literal::Array(
-                es.into_iter().map(|e_| e_.subst_expr(name, e)).collect(),
-                typ,
-            )),
-            Expr::Const(Literal::Tuple(elems)) => Expr::Const(Literal::Tuple(
-                elems.into_iter().map(|e_| e_.subst_expr(name, e)).collect(),
-            )),
-            Expr::Const(Literal::ExternalFunc(_, _)) => self,
-            Expr::Let(ref name_, _, _, _) if name_ == name => self,
-            Expr::Let(name_, typ, box e1, box e2) => Expr::Let(
-                name_,
-                typ,
-                box e1.subst_expr(name, e),
-                box e2.subst_expr(name, e),
-            ),
-            Expr::Var(name_) if &name_ == name => e.clone(),
-            Expr::Var(_) => self,
-            Expr::Load(box e_) => Expr::Load(box e_.subst_expr(name, e)),
-            Expr::Assign(box e1, box e2) => {
-                Expr::Assign(box e1.subst_expr(name, e), box e2.subst_expr(name, e))
+    /// Names referred to by `self` and not bound by `Let` in it, in order of appearance.
+    pub fn free_vars(&self) -> Vec<Ident> {
+        let mut collector = FreeVars {
+            bound: vec![],
+            vars: vec![],
+        };
+        collector.visit_expr(self);
+        collector.vars
+    }
+
+    /// Whether every free occurrence of `name` in `self` is `Load(Var(name))`,
+    /// i.e. the variable is never assigned to nor has its address taken.
+    pub fn is_only_loaded(&self, name: &Ident) -> bool {
+        let mut checker = UseChecker(name, true);
+        checker.visit_expr(self);
+        checker.1
+    }
+
+    /// Names occurring in `self` other than by `Load(Var(name))`, regardless of shadowing.
+    /// A `Let` variable not in this set keeps its initial value.
+    pub fn escaping_vars(&self) -> HashSet<Ident> {
+        let mut collector = EscapingVars(HashSet::new());
+        collector.visit_expr(self);
+        collector.0
+    }
+
+    /// Substitutes `e` for free occurrences of `name` in `self`.
+    /// Binders which would capture free variables of `e` are renamed with names from `names`.
+    pub fn subst_expr(self: Expr, name: &Ident, e: &Expr, names: &mut NameSupply) -> Expr {
+        Subst {
+            name,
+            e,
+            free_vars: e.free_vars(),
+            names,
+        }
+        .fold_expr(self)
+    }
+
+    /// Whether `self` and `other` are the same up to renaming of `Let` binders.
+    pub fn alpha_eq(&self, other: &Expr) -> bool {
+        // binders of both are renamed in order to the same names, which occur in neither
+        let mut names = NameSupply::new();
+        names.visit_expr(self);
+        names.visit_expr(other);
+        let mut canonicalizer = Canonicalizer {
+            names: names.clone(),
+            binders: Env::new(),
+        };
+        let e1 = canonicalizer.fold_expr(self.clone());
+        let mut canonicalizer = Canonicalizer {
+            names,
+            binders: Env::new(),
+        };
+        e1 == canonicalizer.fold_expr(other.clone())
+    }
+}
+
+struct Subst<'a> {
+    name: &'a Ident,
+    e: &'a Expr,
+    free_vars: Vec<Ident>,
+    names: &'a mut NameSupply,
+}
+
+impl<'a> Folder for Subst<'a> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Let(name, typ, box e1, box e2) => {
+                let e1 = self.fold_expr(e1);
+                if &name == self.name {
+                    // `name` is shadowed in e2
+                    Expr::Let(name, typ, box e1, box e2)
+                } else if self.free_vars.contains(&name) {
+                    // the binder would capture a free variable of `e`, so it is renamed
+                    let fresh = self.names.fresh(&name);
+                    let e2 = e2.subst_expr(&name, &Expr::Var(fresh.clone()), self.names);
+                    Expr::Let(fresh, typ, box e1, box self.fold_expr(e2))
+                } else {
+                    Expr::Let(name, typ, box e1, box self.fold_expr(e2))
+                }
             }
-            Expr::Call(box f, args) => Expr::Call(
-                box f.subst_expr(name, e),
-                args.into_iter().map(|e_| e_.subst_expr(name, e)).collect(),
-            ),
-            Expr::If(box cond, box e1, box e2) => Expr::If(
-                box cond.subst_expr(name, e),
-                box e1.subst_expr(name, e),
-                box e2.subst_expr(name, e),
-            ),
-            Expr::BinOp(op, box e1, box e2) => {
-                Expr::BinOp(op, box e1.subst_expr(name, e), box e2.subst_expr(name, e))
+            Expr::Var(ref name) if name == self.name => self.e.clone(),
+            e => visit::fold_expr(self, e),
+        }
+    }
+}
+
+// renames `Let` binders to names from `names` in the order they are folded
+struct Canonicalizer {
+    names: NameSupply,
+    // binders in scope to their new names
+    binders: Env<Ident>,
+}
+
+impl Folder for Canonicalizer {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Let(name, typ, box e1, box e2) => {
+                let e1 = self.fold_expr(e1);
+                let fresh = self.names.fresh(&Ident::new(""));
+                let outer = self.binders.clone();
+                self.binders = self.binders.add(name, fresh.clone());
+                let e2 = self.fold_expr(e2);
+                self.binders = outer;
+                Expr::Let(fresh, typ, box e1, box e2)
+            }
+            Expr::Var(name) => Expr::Var(self.binders.lookup(&name).unwrap_or(name)),
+            e => visit::fold_expr(self, e),
+        }
+    }
+}
+
+struct FreeVars {
+    bound: Vec<Ident>,
+    vars: Vec<Ident>,
+}
+
+impl<'a> Visitor<'a> for FreeVars {
+    fn visit_expr(&mut self, e: &'a Expr) {
+        match *e {
+            Expr::Let(ref name, _, box ref e1, box ref e2) => {
+                self.visit_expr(e1);
+                self.bound.push(name.clone());
+                self.visit_expr(e2);
+                self.bound.pop();
             }
-            Expr::ArrayAt(box arr, box idx) => {
-                Expr::ArrayAt(box arr.subst_expr(name, e), box idx.subst_expr(name, e))
+            Expr::Var(ref name) => {
+                if !self.bound.contains(name) && !self.vars.contains(name) {
+                    self.vars.push(name.clone());
+                }
+            }
+            _ => visit::walk_expr(self, e),
+        }
+    }
+}
+
+struct UseChecker<'b>(&'b Ident, bool);
+
+impl<'a, 'b> Visitor<'a> for UseChecker<'b> {
+    fn visit_expr(&mut self, e: &'a Expr) {
+        match *e {
+            Expr::Load(box Expr::Var(ref name)) if name == self.0 => (),
+            Expr::Var(ref name) if name == self.0 => self.1 = false,
+            Expr::Let(ref name, _, box ref e1, _) if name == self.0 => self.visit_expr(e1),
+            _ => visit::walk_expr(self, e),
+        }
+    }
+}
+
+struct EscapingVars(HashSet<Ident>);
+
+impl<'a> Visitor<'a> for EscapingVars {
+    fn visit_expr(&mut self, e: &'a Expr) {
+        match *e {
+            Expr::Load(box Expr::Var(_)) => (),
+            Expr::Var(ref name) => {
+                self.0.insert(name.clone());
             }
-            Expr::TupleAt(box e_, label) => Expr::TupleAt(box e_.subst_expr(name, e), label),
-            Expr::PrintNum(box e_) => Expr::PrintNum(box e_.subst_expr(name, e)),
+            _ => visit::walk_expr(self, e),
         }
     }
 }
@@ -0,0 +1,95 @@
+use super::*;
+
+fn var(name: &str) -> Expr {
+    Expr::Var(Ident::new(name))
+}
+
+fn load(name: &str) -> Expr {
+    Expr::Load(box var(name))
+}
+
+#[test]
+fn shadowing_test() {
+    // (let x: Int = load x in load x)[x := y]
+    //   == let x: Int = load y in load x
+    let e = Expr::Let(Ident::new("x"), Type::Int, box load("x"), box load("x"));
+    assert_eq!(
+        e.subst_expr(&Ident::new("x"), &var("y"), &mut NameSupply::new()),
+        Expr::Let(Ident::new("x"), Type::Int, box load("y"), box load("x")),
+    );
+}
+
+#[test]
+fn capture_avoiding_test() {
+    // (let y: Int = 1 in (load x) + (load y))[x := y]
+    //   == let y': Int = 1 in (load y) + (load y')
+    let e = Expr::Let(
+        Ident::new("y"),
+        Type::Int,
+        box Expr::Const(Literal::Int(1)),
+        box Expr::BinOp(BinOp::Add, box load("x"), box load("y")),
+    );
+    let result = e.subst_expr(&Ident::new("x"), &var("y"), &mut NameSupply::new());
+    let expected = Expr::Let(
+        Ident::new("z"),
+        Type::Int,
+        box Expr::Const(Literal::Int(1)),
+        box Expr::BinOp(BinOp::Add, box load("y"), box load("z")),
+    );
+    assert!(result.alpha_eq(&expected), "{:?}", result);
+    assert_eq!(result.free_vars(), vec![Ident::new("y")]);
+}
+
+#[test]
+fn free_vars_test() {
+    // let a: Int = load b in f(load a, load c, load b)
+    let e = Expr::Let(
+        Ident::new("a"),
+        Type::Int,
+        box load("b"),
+        box Expr::Call(box var("f"), vec![load("a"), load("c"), load("b")]),
+    );
+    assert_eq!(
+        e.free_vars(),
+        vec![Ident::new("b"), Ident::new("f"), Ident::new("c")]
+    );
+}
+
+#[test]
+fn escaping_vars_test() {
+    // let a: Int = load b in (a := load c; f(b))
+    let e = Expr::Let(
+        Ident::new("a"),
+        Type::Int,
+        box load("b"),
+        box Expr::Call(
+            box var("f"),
+            vec![Expr::Assign(box var("a"), box load("c")), var("b")],
+        ),
+    );
+    let escaping = e.escaping_vars();
+    assert_eq!(escaping.len(), 3);
+    assert!(["a", "b", "f"].iter().all(|name| escaping.contains(&Ident::new(name))));
+    assert!(!e.is_only_loaded(&Ident::new("b")));
+    assert!(e.is_only_loaded(&Ident::new("c")));
+}
+
+#[test]
+fn alpha_eq_test() {
+    let let_in = |name: &str, body: Expr| {
+        Expr::Let(
+            Ident::new(name),
+            Type::Int,
+            box Expr::Const(Literal::Int(0)),
+            box body,
+        )
+    };
+    assert!(let_in("a", load("a")).alpha_eq(&let_in("b", load("b"))));
+    // free variables must be the same
+    assert!(!let_in("a", load("c")).alpha_eq(&let_in("b", load("d"))));
+    // a bound variable is not equal to a free one of the same name
+    assert!(!let_in("a", load("a")).alpha_eq(&let_in("b", load("a"))));
+    // the innermost binder is used
+    assert!(let_in("a", let_in("a", load("a"))).alpha_eq(&let_in("a", let_in("b", load("b")))));
+    assert!(!let_in("a", let_in("b", load("a"))).alpha_eq(&let_in("a", let_in("b", load("b")))));
+}
@@ -3,105 +3,200 @@ pub mod error;
 #[cfg(test)]
 mod test;
 
-use crate::{env::Env, *};
+use crate::{env::Env, interface::Decl, is_constant::Constant, *};
 use error::Error;
+use std::collections::HashSet;
 
 pub fn check(nf: &Nf) -> Result<Option<Type>, Error> {
+    Ok(typing(nf)?.body.map(|body| body.typ))
+}
+
+pub fn typing(nf: &Nf) -> Result<typed::Nf, Error> {
+    typing_with_imports(nf, &[])
+}
+
+pub fn typing_with_imports(nf: &Nf, interfaces: &[Interface]) -> Result<typed::Nf, Error> {
     let mut env = Env::new();
+    let mut imports = vec![];
+    for decl in interfaces.iter().flat_map(|interface| interface.decls.iter()) {
+        if nf.funcs.iter().any(|func| func.name == decl.name)
+            || imports.iter().any(|import: &Decl| import.name == decl.name)
+        {
+            return Err(Error::ConflictingImport(decl.name.clone()));
+        }
+        env = env.add(decl.name.clone(), Type::Pointer(box decl.typ.clone()));
+        imports.push(decl.clone());
+    }
     for func in nf.funcs.iter() {
         let params: Vec<Type> = func.params.iter().map(|param| param.1.clone()).collect();
         env = env.add(
             func.name.clone(),
-            Type::Pointer(box Type::Func(params, box func.ret_type.clone())),
+            Type::Pointer(box Type::Func(params, box func.ret_type.clone(), false)),
         )
     }
 
+    let mut funcs = vec![];
     for func in nf.funcs.iter() {
         let mut env = env.clone();
         for (ref name, ref ty) in func.params.iter() {
             env = env.add(name.clone(), Type::Pointer(box ty.clone()));
         }
-        check_expr(&func.body, &env)?;
+        funcs.push(typed::Func {
+            name: func.name.clone(),
+            params: func.params.clone(),
+            ret_type: func.ret_type.clone(),
+            body: check_expr(&func.body, &env, &Consts::new(&func.body.escaping_vars()))?,
+            visibility: func.visibility,
+        });
     }
-    Ok(if let Some(ref body) = &nf.body {
-        Some(check_expr(body, &env)?)
+    let body = if let Some(ref body) = &nf.body {
+        Some(check_expr(body, &env, &Consts::new(&body.escaping_vars()))?)
     } else {
         None
+    };
+    Ok(typed::Nf {
+        imports,
+        funcs,
+        body,
     })
 }
 
-fn check_expr(e: &Expr, env: &Env<Type>) -> Result<Type, Error> {
+// values of `Let` variables known at compile time, for checking constant indices
+struct Consts<'a> {
+    values: Env<Option<Constant>>,
+    // variables which may change after their initialization, see `Expr::escaping_vars`
+    escaping: &'a HashSet<Ident>,
+}
+
+impl<'a> Consts<'a> {
+    fn new(escaping: &'a HashSet<Ident>) -> Consts<'a> {
+        Consts {
+            values: Env::new(),
+            escaping,
+        }
+    }
+
+    fn add(&self, name: &Ident, init: &Expr) -> Consts<'a> {
+        let value = if self.escaping.contains(name) {
+            None
+        } else {
+            is_constant::eval_expr(init, &self.values)
+        };
+        Consts {
+            values: self.values.add(name.clone(), value),
+            escaping: self.escaping,
+        }
+    }
+}
+
+fn check_expr(e: &Expr, env: &Env<Type>, consts: &Consts) -> Result<typed::Expr, Error> {
+    use typed::ExprKind;
     match e {
-        Expr::Const(lit) => check_literal(lit, env),
+        Expr::Const(lit) => check_literal(lit, env, consts),
         Expr::Let(ref name, ref typ, box ref e1, box ref e2) => {
-            let typ_ = check_expr(e1, env)?;
-            if typ != &typ_ {
-                return Err(Error::UnmatchLet(e1.clone(), typ_));
+            let e1_ = check_expr(e1, env, consts)?;
+            if typ != &e1_.typ {
+                return Err(Error::UnmatchLet(e1.clone(), e1_.typ));
             }
             let mut env = env.clone();
-            env = env.add(name.clone(), Type::Pointer(box typ_));
-            check_expr(e2, &env)
+            env = env.add(name.clone(), Type::Pointer(box e1_.typ.clone()));
+            let consts = consts.add(name, e1);
+            let e2 = check_expr(e2, &env, &consts)?;
+            let ty = e2.typ.clone();
+            Ok(typed::Expr::new(
+                ExprKind::Let(name.clone(), typ.clone(), box e1_, box e2),
+                ty,
+            ))
+        }
+        Expr::Var(ref name) => {
+            let ty = env
+                .lookup(name)
+                .ok_or(Error::UnboundVariable(name.clone()))?;
+            Ok(typed::Expr::new(ExprKind::Var(name.clone()), ty))
         }
-        Expr::Var(ref name) => env.lookup(name).ok_or(Error::UnboundVariable(name.clone())),
         Expr::Load(box ref e) => {
-            if let Type::Pointer(box ty) = check_expr(e, env)? {
-                Ok(ty)
+            let e_ = check_expr(e, env, consts)?;
+            if let Type::Pointer(box ty) = e_.typ.clone() {
+                Ok(typed::Expr::new(ExprKind::Load(box e_), ty))
             } else {
                 Err(Error::DereferenceNonpointer(e.clone()))
             }
         }
         Expr::Assign(box ref e1, box ref e2) => {
-            if let Type::Pointer(box ty1) = check_expr(e1, env)? {
-                let ty2 = check_expr(e2, env)?;
-                if ty1 == ty2 {
-                    Ok(Type::Pointer(box ty1))
+            let e1_ = check_expr(e1, env, consts)?;
+            if let Type::Pointer(box ty1) = e1_.typ.clone() {
+                let e2_ = check_expr(e2, env, consts)?;
+                if ty1 == e2_.typ {
+                    Ok(typed::Expr::new(
+                        ExprKind::Assign(box e1_, box e2_),
+                        Type::Pointer(box ty1),
+                    ))
                 } else {
-                    Err(Error::UnmatchAssign(e2.clone(), ty1, ty2))
+                    Err(Error::UnmatchAssign(e2.clone(), ty1, e2_.typ))
                 }
             } else {
                 Err(Error::AssignToNonpointer(e1.clone()))
             }
         }
         Expr::Call(box ref e, ref args) => {
-            let e_ty = check_expr(e, env)?;
-            if let Type::Pointer(box Type::Func(params, box ret_type)) = e_ty {
-                let args: Result<_, _> = args.iter().map(|arg| check_expr(arg, env)).collect();
-                let args: Vec<Type> = args?;
-                if params == args {
-                    Ok(ret_type)
+            let e_ = check_expr(e, env, consts)?;
+            if let Type::Pointer(box Type::Func(params, box ret_type, is_variadic)) = e_.typ.clone()
+            {
+                let args_: Result<_, _> =
+                    args.iter().map(|arg| check_expr(arg, env, consts)).collect();
+                let args_: Vec<typed::Expr> = args_?;
+                let arg_types: Vec<Type> = args_.iter().map(|arg| arg.typ.clone()).collect();
+                let matched = if is_variadic {
+                    arg_types.len() >= params.len() && params[..] == arg_types[..params.len()]
                 } else {
-                    Err(Error::UnmatchParamsAndArgs(e.clone(), params, args))
+                    params == arg_types
+                };
+                if !matched {
+                    return Err(Error::UnmatchParamsAndArgs(e.clone(), params, arg_types));
                 }
+                // extra args are passed after default argument promotions as in C,
+                // which are defined only for scalar types
+                for (arg, ty) in args.iter().zip(arg_types.iter()).skip(params.len()) {
+                    match ty {
+                        Type::Bool | Type::Char | Type::Int | Type::Pointer(_) => (),
+                        _ => return Err(Error::InvalidVariadicArg(arg.clone(), ty.clone())),
+                    }
+                }
+                Ok(typed::Expr::new(ExprKind::Call(box e_, args_), ret_type))
             } else {
-                Err(Error::ApplyNonFunc(e.clone(), e_ty))
+                Err(Error::ApplyNonFunc(e.clone(), e_.typ))
             }
         }
         Expr::If(box ref cond, box ref e1, box ref e2) => {
-            let cond_ty = check_expr(cond, env)?;
-            if cond_ty == Type::Bool {
-                let ty1 = check_expr(e1, env)?;
-                let ty2 = check_expr(e2, env)?;
-                if ty1 == ty2 {
-                    Ok(ty1)
+            let cond_ = check_expr(cond, env, consts)?;
+            if cond_.typ == Type::Bool {
+                let e1_ = check_expr(e1, env, consts)?;
+                let e2_ = check_expr(e2, env, consts)?;
+                if e1_.typ == e2_.typ {
+                    let ty = e1_.typ.clone();
+                    Ok(typed::Expr::new(
+                        ExprKind::If(box cond_, box e1_, box e2_),
+                        ty,
+                    ))
                 } else {
                     Err(Error::UnmatchIfBranches(
                         Expr::If(box cond.clone(), box e1.clone(), box e2.clone()),
-                        ty1,
-                        ty2,
+                        e1_.typ,
+                        e2_.typ,
                     ))
                 }
             } else {
-                Err(Error::UnmatchIfCond(cond.clone(), cond_ty))
+                Err(Error::UnmatchIfCond(cond.clone(), cond_.typ))
             }
         }
         Expr::BinOp(ref op, box ref e1, box ref e2) => {
-            let ty1 = check_expr(e1, env)?;
-            let ty2 = check_expr(e2, env)?;
-            match (op, ty1, ty2) {
+            let e1_ = check_expr(e1, env, consts)?;
+            let e2_ = check_expr(e2, env, consts)?;
+            let ty = match (op, &e1_.typ, &e2_.typ) {
                 (BinOp::Add, Type::Int, Type::Int)
                 | (BinOp::Sub, Type::Int, Type::Int)
                 | (BinOp::Mult, Type::Int, Type::Int)
-                | (BinOp::Div, Type::Int, Type::Int) => Ok(Type::Int),
+                | (BinOp::Div, Type::Int, Type::Int) => Type::Int,
 
                 (BinOp::Eq, Type::Bool, Type::Bool)
                 | (BinOp::Neq, Type::Bool, Type::Bool)
@@ -110,27 +205,47 @@ fn check_expr(e: &Expr, env: &Env<Type>) -> Result<Type, Error> {
                 | (BinOp::Lt, Type::Int, Type::Int)
                 | (BinOp::Gt, Type::Int, Type::Int)
                 | (BinOp::Leq, Type::Int, Type::Int)
-                | (BinOp::Geq, Type::Int, Type::Int) => Ok(Type::Bool),
-                _ => Err(Error::InvalidBinOp(op.clone(), e1.clone(), e2.clone())),
-            }
+                | (BinOp::Geq, Type::Int, Type::Int) => Type::Bool,
+                _ => return Err(Error::InvalidBinOp(op.clone(), e1.clone(), e2.clone())),
+            };
+            Ok(typed::Expr::new(
+                ExprKind::BinOp(op.clone(), box e1_, box e2_),
+                ty,
+            ))
         }
         Expr::ArrayAt(box ref arr, box ref idx) => {
-            let arr_ty = check_expr(arr, env)?;
-            let idx_ty = check_expr(idx, env)?;
-            if idx_ty == Type::Int {
-                if let Type::Pointer(box Type::Array(box elem_ty, _)) = arr_ty {
-                    Ok(Type::Pointer(box elem_ty))
+            let arr_ = check_expr(arr, env, consts)?;
+            let idx_ = check_expr(idx, env, consts)?;
+            if idx_.typ == Type::Int {
+                if let Type::Pointer(box Type::Array(box elem_ty, len)) = arr_.typ.clone() {
+                    match is_constant::eval_expr(idx, &consts.values) {
+                        Some(Constant::Int(n)) if n < 0 => {
+                            return Err(Error::NegativeIndex(e.clone(), n))
+                        }
+                        Some(Constant::Int(n)) if n as usize >= len => {
+                            return Err(Error::IndexOutOfBounds(e.clone(), n, len))
+                        }
+                        _ => (),
+                    }
+                    Ok(typed::Expr::new(
+                        ExprKind::ArrayAt(box arr_, box idx_),
+                        Type::Pointer(box elem_ty),
+                    ))
                 } else {
-                    Err(Error::IndexingForNonArray(arr.clone(), arr_ty))
+                    Err(Error::IndexingForNonArray(arr.clone(), arr_.typ))
                 }
             } else {
-                Err(Error::IndexingWithNonInteger(idx.clone(), idx_ty))
+                Err(Error::IndexingWithNonInteger(idx.clone(), idx_.typ))
             }
         }
         Expr::TupleAt(box ref e, ref idx) => {
-            if let Type::Pointer(box Type::Tuple(elems)) = check_expr(e, env)? {
+            let e_ = check_expr(e, env, consts)?;
+            if let Type::Pointer(box Type::Tuple(elems)) = e_.typ.clone() {
                 if let Some(ty) = elems.into_iter().nth(*idx) {
-                    Ok(Type::Pointer(box ty))
+                    Ok(typed::Expr::new(
+                        ExprKind::TupleAt(box e_, *idx),
+                        Type::Pointer(box ty),
+                    ))
                 } else {
                     Err(Error::InvalidTupleAccess(e.clone(), *idx))
                 }
@@ -139,30 +254,42 @@ fn check_expr(e: &Expr, env: &Env<Type>) -> Result<Type, Error> {
             }
         }
         Expr::PrintNum(box ref e) => {
-            check_expr(e, env)?;
-            Ok(Type::Void)
+            let e_ = check_expr(e, env, consts)?;
+            Ok(typed::Expr::new(ExprKind::PrintNum(box e_), Type::Void))
         }
     }
 }
 
-fn check_literal(lit: &Literal, env: &Env<Type>) -> Result<Type, Error> {
+fn check_literal(lit: &Literal, env: &Env<Type>, consts: &Consts) -> Result<typed::Expr, Error> {
+    use typed::ExprKind::Const;
     match lit {
-        Literal::Bool(_) => Ok(Type::Bool),
-        Literal::Char(_) => Ok(Type::Char),
-        Literal::Int(_) => Ok(Type::Int),
+        Literal::Bool(b) => Ok(typed::Expr::new(Const(typed::Literal::Bool(*b)), Type::Bool)),
+        Literal::Char(c) => Ok(typed::Expr::new(Const(typed::Literal::Char(*c)), Type::Char)),
+        Literal::Int(n) => Ok(typed::Expr::new(Const(typed::Literal::Int(*n)), Type::Int)),
         Literal::Array(elems, ref ty) => {
+            let mut elems_ = vec![];
             for e in elems.iter() {
-                let given_ty = check_expr(e, env)?;
-                if ty != &given_ty {
+                let e_ = check_expr(e, env, consts)?;
+                if ty != &e_.typ {
                     return Err(Error::UnmatchArrayElem(e.clone(), ty.clone()));
                 }
+                elems_.push(e_);
             }
-            Ok(Type::Array(box ty.clone(), elems.len()))
+            Ok(typed::Expr::new(
+                Const(typed::Literal::Array(elems_, ty.clone())),
+                Type::Array(box ty.clone(), elems.len()),
+            ))
         }
         Literal::Tuple(ref elems) => {
-            let elems: Result<Vec<Type>, _> = elems.iter().map(|e| check_expr(e, env)).collect();
-            Ok(Type::Tuple(elems?))
+            let elems: Result<Vec<typed::Expr>, _> =
+                elems.iter().map(|e| check_expr(e, env, consts)).collect();
+            let elems = elems?;
+            let ty = Type::Tuple(elems.iter().map(|e| e.typ.clone()).collect());
+            Ok(typed::Expr::new(Const(typed::Literal::Tuple(elems)), ty))
         }
-        Literal::ExternalFunc(_, typ) => Ok(Type::Pointer(box typ.clone())),
+        Literal::ExternalFunc(ref name, typ, cc) => Ok(typed::Expr::new(
+            Const(typed::Literal::ExternalFunc(name.clone(), typ.clone(), *cc)),
+            Type::Pointer(box typ.clone()),
+        )),
     }
 }
@@ -1,4 +1,5 @@
 use super::*;
+use crate::ident::NameSupply;
 use std::error;
 use std::fmt;
 
@@ -16,9 +17,47 @@ pub enum Error {
     InvalidBinOp(BinOp, Expr, Expr),
     IndexingForNonArray(Expr, Type),
     IndexingWithNonInteger(Expr, Type),
+    NegativeIndex(Expr, i32),
+    IndexOutOfBounds(Expr, i32, usize),
     UnmatchArrayElem(Expr, Type),
     InvalidTupleAccess(Expr, usize),
     IndexingForNonTuple(Expr),
+    ConflictingImport(Ident),
+    InvalidVariadicArg(Expr, Type),
+}
+
+impl Error {
+    /// Restores the source names of the identifiers renamed by `resolve` in `self`,
+    /// which would otherwise be shown as e.g. `x.0`.
+    pub fn with_source_names(mut self, names: &NameSupply) -> Error {
+        use Error::*;
+        match self {
+            UnboundVariable(ref mut name) | ConflictingImport(ref mut name) => {
+                *name = names.source_name(name).clone()
+            }
+            UnmatchLet(ref mut e, _)
+            | UnmatchParamsAndArgs(ref mut e, _, _)
+            | ApplyNonFunc(ref mut e, _)
+            | UnmatchIfBranches(ref mut e, _, _)
+            | UnmatchIfCond(ref mut e, _)
+            | DereferenceNonpointer(ref mut e)
+            | AssignToNonpointer(ref mut e)
+            | UnmatchAssign(ref mut e, _, _)
+            | IndexingForNonArray(ref mut e, _)
+            | IndexingWithNonInteger(ref mut e, _)
+            | NegativeIndex(ref mut e, _)
+            | IndexOutOfBounds(ref mut e, _, _)
+            | UnmatchArrayElem(ref mut e, _)
+            | InvalidTupleAccess(ref mut e, _)
+            | IndexingForNonTuple(ref mut e)
+            | InvalidVariadicArg(ref mut e, _) => names.restore_source_names(e),
+            InvalidBinOp(_, ref mut e1, ref mut e2) => {
+                names.restore_source_names(e1);
+                names.restore_source_names(e2);
+            }
+        }
+        self
+    }
 }
 
 impl fmt::Display for Error {
@@ -78,9 +117,21 @@ impl fmt::Display for Error {
                 "indexing expr {} must have integer type, but have {}",
                 e, ty
             ),
+            NegativeIndex(e, idx) => write!(f, "in {}, index {} is negative", e, idx),
+            IndexOutOfBounds(e, idx, len) => write!(
+                f,
+                "in {}, index {} is out of bounds for length {}",
+                e, idx, len
+            ),
             UnmatchArrayElem(e, ty) => write!(f, "elem {} in array must have {}", e, ty),
             InvalidTupleAccess(e, idx) => write!(f, "invalid access of expr {}, with {}", e, idx),
             IndexingForNonTuple(e) => write!(f, "{} is not struct expr", e),
+            ConflictingImport(name) => write!(f, "imported {} is already defined", name),
+            InvalidVariadicArg(e, ty) => write!(
+                f,
+                "{} can not be passed as variadic argument, since it has type {}",
+                e, ty
+            ),
         }
     }
 }
@@ -1,5 +1,6 @@
 use super::*;
 use crate::*;
+use super::error::Error;
 
 #[test]
 fn primitive_test() {
@@ -31,12 +32,13 @@ fn func_test() {
             params: vec![],
             ret_type: Type::Int,
             body: Expr::Const(Literal::Int(42)),
+            visibility: Visibility::Public,
         }],
         body: Some(Expr::Var(Ident::new("a"))),
     };
     assert_eq!(
         check(&nf),
-        Ok(Some(Type::Pointer(box Type::Func(vec![], box Type::Int))))
+        Ok(Some(Type::Pointer(box Type::Func(vec![], box Type::Int, false))))
     );
 }
 
@@ -49,6 +51,7 @@ fn apply_test() {
             params: vec![],
             ret_type: Type::Int,
             body: Expr::Const(Literal::Int(42)),
+            visibility: Visibility::Public,
         }],
         body: Some(Expr::Call(box Expr::Var(Ident::new("a")), vec![])),
     };
@@ -79,3 +82,219 @@ fn array_test() {
     };
     assert_eq!(check(&nf), Ok(Some(Type::Int)));
 }
+
+#[test]
+fn typing_test() {
+    // let a: int = 1;
+    // if true then (load a) else 2
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("a"),
+            Type::Int,
+            box Expr::Const(Literal::Int(1)),
+            box Expr::If(
+                box Expr::Const(Literal::Bool(true)),
+                box Expr::Load(box Expr::Var(Ident::new("a"))),
+                box Expr::Const(Literal::Int(2)),
+            ),
+        )),
+    };
+    let body = typing(&nf).unwrap().body.unwrap();
+    assert_eq!(body.typ, Type::Int);
+    if let typed::ExprKind::Let(_, _, box e1, box e2) = body.kind {
+        assert_eq!(e1.typ, Type::Int);
+        assert_eq!(e2.typ, Type::Int);
+        if let typed::ExprKind::If(_, box then_, _) = e2.kind {
+            if let typed::ExprKind::Load(box var) = then_.kind {
+                assert_eq!(var.typ, Type::Pointer(box Type::Int));
+            } else {
+                panic!("then-branch must be load");
+            }
+        } else {
+            panic!("body of let must be if-expr");
+        }
+    } else {
+        panic!("body must be let-expr");
+    }
+}
+
+#[test]
+fn import_test() {
+    use crate::interface::{Decl, Interface};
+    let lib = Interface {
+        name: "lib".to_string(),
+        decls: vec![Decl {
+            name: Ident::new("twice"),
+            symbol: "twice".to_string(),
+            typ: Type::Func(vec![Type::Int], box Type::Int, false),
+        }],
+    };
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Call(
+            box Expr::Var(Ident::new("twice")),
+            vec![Expr::Const(Literal::Int(21))],
+        )),
+    };
+    assert_eq!(
+        typing_with_imports(&nf, &[lib.clone()]).unwrap().body.unwrap().typ,
+        Type::Int
+    );
+    assert_eq!(
+        check(&nf),
+        Err(Error::UnboundVariable(Ident::new("twice")))
+    );
+
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Call(
+            box Expr::Var(Ident::new("twice")),
+            vec![Expr::Const(Literal::Bool(true))],
+        )),
+    };
+    assert_eq!(
+        typing_with_imports(&nf, &[lib]),
+        Err(Error::UnmatchParamsAndArgs(
+            Expr::Var(Ident::new("twice")),
+            vec![Type::Int],
+            vec![Type::Bool]
+        ))
+    );
+}
+
+#[test]
+fn variadic_test() {
+    let printf = Expr::Const(Literal::ExternalFunc(
+        "printf".to_string(),
+        Type::Func(vec![Type::Pointer(box Type::Char)], box Type::Int, true),
+        CallConv::C,
+    ));
+    let format = Expr::ArrayAt(
+        box Expr::Var(Ident::new("format")),
+        box Expr::Const(Literal::Int(0)),
+    );
+    let call = |args: Vec<Expr>| Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("format"),
+            Type::Array(box Type::Char, 1),
+            box Expr::Const(Literal::Array(
+                vec![Expr::Const(Literal::Char('\0'))],
+                Type::Char,
+            )),
+            box Expr::Call(box printf.clone(), args),
+        )),
+    };
+
+    let nf = call(vec![
+        format.clone(),
+        Expr::Const(Literal::Int(1)),
+        Expr::Const(Literal::Char('a')),
+        Expr::Const(Literal::Bool(true)),
+    ]);
+    assert_eq!(check(&nf), Ok(Some(Type::Int)));
+
+    let nf = call(vec![]);
+    assert_eq!(
+        check(&nf),
+        Err(Error::UnmatchParamsAndArgs(
+            printf.clone(),
+            vec![Type::Pointer(box Type::Char)],
+            vec![]
+        ))
+    );
+
+    let tuple = Expr::Const(Literal::Tuple(vec![Expr::Const(Literal::Int(1))]));
+    let nf = call(vec![format, tuple.clone()]);
+    assert_eq!(
+        check(&nf),
+        Err(Error::InvalidVariadicArg(tuple, Type::Tuple(vec![Type::Int])))
+    );
+}
+
+#[test]
+fn shadowing_test() {
+    // let x: Int = 1 in let x: Bool = true in load x
+    let nf = Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("x"),
+            Type::Int,
+            box Expr::Const(Literal::Int(1)),
+            box Expr::Let(
+                Ident::new("x"),
+                Type::Bool,
+                box Expr::Const(Literal::Bool(true)),
+                box Expr::Load(box Expr::Var(Ident::new("x"))),
+            ),
+        )),
+    };
+    assert_eq!(check(&nf), Ok(Some(Type::Bool)));
+}
+
+#[test]
+fn constant_index_test() {
+    // int[2] arr = {1, 2};
+    // int i = <init>;
+    // return load arr[<idx>];
+    let nf = |init: Expr, idx: Expr| Nf {
+        funcs: vec![],
+        body: Some(Expr::Let(
+            Ident::new("arr"),
+            Type::Array(box Type::Int, 2),
+            box Expr::Const(Literal::Array(
+                vec![Expr::Const(Literal::Int(1)), Expr::Const(Literal::Int(2))],
+                Type::Int,
+            )),
+            box Expr::Let(
+                Ident::new("i"),
+                Type::Int,
+                box init,
+                box Expr::Load(box Expr::ArrayAt(box Expr::Var(Ident::new("arr")), box idx)),
+            ),
+        )),
+    };
+    let int = |n| Expr::Const(Literal::Int(n));
+    let load_i = || Expr::Load(box Expr::Var(Ident::new("i")));
+    let at = |idx| Expr::ArrayAt(box Expr::Var(Ident::new("arr")), box idx);
+
+    assert_eq!(check(&nf(int(0), int(1))), Ok(Some(Type::Int)));
+    assert_eq!(
+        check(&nf(int(0), int(2))),
+        Err(Error::IndexOutOfBounds(at(int(2)), 2, 2))
+    );
+    assert_eq!(
+        check(&nf(int(0), int(-1))),
+        Err(Error::NegativeIndex(at(int(-1)), -1))
+    );
+
+    // the value of `i` is evaluated
+    let init = Expr::BinOp(BinOp::Add, box int(1), box int(2));
+    assert_eq!(
+        check(&nf(init, load_i())),
+        Err(Error::IndexOutOfBounds(at(load_i()), 3, 2))
+    );
+    let idx = Expr::BinOp(BinOp::Sub, box load_i(), box int(2));
+    assert_eq!(
+        check(&nf(int(1), idx.clone())),
+        Err(Error::NegativeIndex(at(idx), -1))
+    );
+
+    // indices not known at compile time are left to runtime
+    let init = Expr::BinOp(BinOp::Div, box int(1), box int(0));
+    assert_eq!(check(&nf(init, load_i())), Ok(Some(Type::Int)));
+
+    // int i = 2; i = 0; return load arr[load i];
+    let mut assigned = nf(int(2), load_i());
+    if let Some(Expr::Let(_, _, _, box Expr::Let(_, _, _, box ref mut e2))) = assigned.body {
+        let access = e2.clone();
+        *e2 = Expr::Let(
+            Ident::new("dummy"),
+            Type::Pointer(box Type::Int),
+            box Expr::Assign(box Expr::Var(Ident::new("i")), box int(0)),
+            box access,
+        );
+    }
+    assert_eq!(check(&assigned), Ok(Some(Type::Int)));
+}
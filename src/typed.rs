@@ -0,0 +1,54 @@
+use crate::{interface::Decl, BinOp, CallConv, Ident, Type, Visibility};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nf {
+    pub imports: Vec<Decl>,
+    pub funcs: Vec<Func>,
+    pub body: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Func {
+    pub name: Ident,
+    pub params: Vec<(Ident, Type)>,
+    pub ret_type: Type,
+    pub body: Expr,
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub typ: Type,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExprKind {
+    Const(Literal),
+    Let(Ident, Type, Box<Expr>, Box<Expr>),
+    Var(Ident),
+    Load(Box<Expr>),
+    Assign(Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    BinOp(BinOp, Box<Expr>, Box<Expr>),
+    ArrayAt(Box<Expr>, Box<Expr>),
+    TupleAt(Box<Expr>, usize),
+    PrintNum(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Literal {
+    Bool(bool),
+    Char(char),
+    Int(i32),
+    Array(Vec<Expr>, Type),
+    Tuple(Vec<Expr>),
+    ExternalFunc(String, Type, CallConv),
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, typ: Type) -> Expr {
+        Expr { kind, typ }
+    }
+}
@@ -0,0 +1,327 @@
+#[cfg(test)]
+mod test;
+
+use crate::*;
+
+/// Traversal of the syntax tree by reference.
+///
+/// Each method defaults to the `walk_*` function of the same node, which visits its children.
+/// An implementation overrides only the nodes it cares about, and calls the `walk_*` function
+/// to keep traversing into their children.
+///
+/// There is no counterpart over `typed`: its only consumers, `typecheck` which produces it
+/// and `codegen` which lowers it, translate every node by a rule of its own rather than
+/// traverse the tree, so a new node needs a new rule in each of them anyway.
+pub trait Visitor<'a> {
+    fn visit_nf(&mut self, nf: &'a Nf) {
+        walk_nf(self, nf)
+    }
+    fn visit_func(&mut self, func: &'a Func) {
+        walk_func(self, func)
+    }
+    fn visit_expr(&mut self, e: &'a Expr) {
+        walk_expr(self, e)
+    }
+    fn visit_literal(&mut self, lit: &'a Literal) {
+        walk_literal(self, lit)
+    }
+    fn visit_type(&mut self, ty: &'a Type) {
+        walk_type(self, ty)
+    }
+}
+
+pub fn walk_nf<'a, V: Visitor<'a> + ?Sized>(v: &mut V, nf: &'a Nf) {
+    for func in nf.funcs.iter() {
+        v.visit_func(func);
+    }
+    if let Some(ref body) = nf.body {
+        v.visit_expr(body);
+    }
+}
+
+pub fn walk_func<'a, V: Visitor<'a> + ?Sized>(v: &mut V, func: &'a Func) {
+    for (_, ty) in func.params.iter() {
+        v.visit_type(ty);
+    }
+    v.visit_type(&func.ret_type);
+    v.visit_expr(&func.body);
+}
+
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(v: &mut V, e: &'a Expr) {
+    match *e {
+        Expr::Const(ref lit) => v.visit_literal(lit),
+        Expr::Let(_, ref ty, box ref e1, box ref e2) => {
+            v.visit_type(ty);
+            v.visit_expr(e1);
+            v.visit_expr(e2);
+        }
+        Expr::Var(_) => (),
+        Expr::Load(box ref e) | Expr::TupleAt(box ref e, _) | Expr::PrintNum(box ref e) => {
+            v.visit_expr(e)
+        }
+        Expr::Assign(box ref e1, box ref e2)
+        | Expr::BinOp(_, box ref e1, box ref e2)
+        | Expr::ArrayAt(box ref e1, box ref e2) => {
+            v.visit_expr(e1);
+            v.visit_expr(e2);
+        }
+        Expr::Call(box ref f, ref args) => {
+            v.visit_expr(f);
+            for arg in args.iter() {
+                v.visit_expr(arg);
+            }
+        }
+        Expr::If(box ref cond, box ref e1, box ref e2) => {
+            v.visit_expr(cond);
+            v.visit_expr(e1);
+            v.visit_expr(e2);
+        }
+    }
+}
+
+pub fn walk_literal<'a, V: Visitor<'a> + ?Sized>(v: &mut V, lit: &'a Literal) {
+    match *lit {
+        Literal::Bool(_) | Literal::Char(_) | Literal::Int(_) => (),
+        Literal::Array(ref elems, ref ty) => {
+            for e in elems.iter() {
+                v.visit_expr(e);
+            }
+            v.visit_type(ty);
+        }
+        Literal::Tuple(ref elems) => {
+            for e in elems.iter() {
+                v.visit_expr(e);
+            }
+        }
+        Literal::ExternalFunc(_, ref ty, _) => v.visit_type(ty),
+    }
+}
+
+pub fn walk_type<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ty: &'a Type) {
+    match *ty {
+        Type::Void | Type::Bool | Type::Char | Type::Int => (),
+        Type::Func(ref params, box ref ret_ty, _) => {
+            for param in params.iter() {
+                v.visit_type(param);
+            }
+            v.visit_type(ret_ty);
+        }
+        Type::Array(box ref ty, _) | Type::Pointer(box ref ty) => v.visit_type(ty),
+        Type::Tuple(ref elems) => {
+            for elem in elems.iter() {
+                v.visit_type(elem);
+            }
+        }
+    }
+}
+
+/// Same as `Visitor`, but the nodes can be modified in place.
+pub trait VisitorMut {
+    fn visit_nf_mut(&mut self, nf: &mut Nf) {
+        walk_nf_mut(self, nf)
+    }
+    fn visit_func_mut(&mut self, func: &mut Func) {
+        walk_func_mut(self, func)
+    }
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        walk_expr_mut(self, e)
+    }
+    fn visit_literal_mut(&mut self, lit: &mut Literal) {
+        walk_literal_mut(self, lit)
+    }
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        walk_type_mut(self, ty)
+    }
+}
+
+pub fn walk_nf_mut<V: VisitorMut + ?Sized>(v: &mut V, nf: &mut Nf) {
+    for func in nf.funcs.iter_mut() {
+        v.visit_func_mut(func);
+    }
+    if let Some(ref mut body) = nf.body {
+        v.visit_expr_mut(body);
+    }
+}
+
+pub fn walk_func_mut<V: VisitorMut + ?Sized>(v: &mut V, func: &mut Func) {
+    for (_, ty) in func.params.iter_mut() {
+        v.visit_type_mut(ty);
+    }
+    v.visit_type_mut(&mut func.ret_type);
+    v.visit_expr_mut(&mut func.body);
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, e: &mut Expr) {
+    match *e {
+        Expr::Const(ref mut lit) => v.visit_literal_mut(lit),
+        Expr::Let(_, ref mut ty, box ref mut e1, box ref mut e2) => {
+            v.visit_type_mut(ty);
+            v.visit_expr_mut(e1);
+            v.visit_expr_mut(e2);
+        }
+        Expr::Var(_) => (),
+        Expr::Load(box ref mut e)
+        | Expr::TupleAt(box ref mut e, _)
+        | Expr::PrintNum(box ref mut e) => v.visit_expr_mut(e),
+        Expr::Assign(box ref mut e1, box ref mut e2)
+        | Expr::BinOp(_, box ref mut e1, box ref mut e2)
+        | Expr::ArrayAt(box ref mut e1, box ref mut e2) => {
+            v.visit_expr_mut(e1);
+            v.visit_expr_mut(e2);
+        }
+        Expr::Call(box ref mut f, ref mut args) => {
+            v.visit_expr_mut(f);
+            for arg in args.iter_mut() {
+                v.visit_expr_mut(arg);
+            }
+        }
+        Expr::If(box ref mut cond, box ref mut e1, box ref mut e2) => {
+            v.visit_expr_mut(cond);
+            v.visit_expr_mut(e1);
+            v.visit_expr_mut(e2);
+        }
+    }
+}
+
+pub fn walk_literal_mut<V: VisitorMut + ?Sized>(v: &mut V, lit: &mut Literal) {
+    match *lit {
+        Literal::Bool(_) | Literal::Char(_) | Literal::Int(_) => (),
+        Literal::Array(ref mut elems, ref mut ty) => {
+            for e in elems.iter_mut() {
+                v.visit_expr_mut(e);
+            }
+            v.visit_type_mut(ty);
+        }
+        Literal::Tuple(ref mut elems) => {
+            for e in elems.iter_mut() {
+                v.visit_expr_mut(e);
+            }
+        }
+        Literal::ExternalFunc(_, ref mut ty, _) => v.visit_type_mut(ty),
+    }
+}
+
+pub fn walk_type_mut<V: VisitorMut + ?Sized>(v: &mut V, ty: &mut Type) {
+    match *ty {
+        Type::Void | Type::Bool | Type::Char | Type::Int => (),
+        Type::Func(ref mut params, box ref mut ret_ty, _) => {
+            for param in params.iter_mut() {
+                v.visit_type_mut(param);
+            }
+            v.visit_type_mut(ret_ty);
+        }
+        Type::Array(box ref mut ty, _) | Type::Pointer(box ref mut ty) => v.visit_type_mut(ty),
+        Type::Tuple(ref mut elems) => {
+            for elem in elems.iter_mut() {
+                v.visit_type_mut(elem);
+            }
+        }
+    }
+}
+
+/// Traversal of the syntax tree by value, rebuilding it from the folded nodes.
+/// Each method defaults to the `fold_*` function of the same node.
+pub trait Folder {
+    fn fold_nf(&mut self, nf: Nf) -> Nf {
+        fold_nf(self, nf)
+    }
+    fn fold_func(&mut self, func: Func) -> Func {
+        fold_func(self, func)
+    }
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        fold_expr(self, e)
+    }
+    fn fold_literal(&mut self, lit: Literal) -> Literal {
+        fold_literal(self, lit)
+    }
+    fn fold_type(&mut self, ty: Type) -> Type {
+        fold_type(self, ty)
+    }
+}
+
+pub fn fold_nf<F: Folder + ?Sized>(f: &mut F, nf: Nf) -> Nf {
+    Nf {
+        funcs: nf.funcs.into_iter().map(|func| f.fold_func(func)).collect(),
+        body: nf.body.map(|body| f.fold_expr(body)),
+    }
+}
+
+pub fn fold_func<F: Folder + ?Sized>(f: &mut F, func: Func) -> Func {
+    Func {
+        name: func.name,
+        params: func
+            .params
+            .into_iter()
+            .map(|(name, ty)| (name, f.fold_type(ty)))
+            .collect(),
+        ret_type: f.fold_type(func.ret_type),
+        body: f.fold_expr(func.body),
+        visibility: func.visibility,
+    }
+}
+
+pub fn fold_expr<F: Folder + ?Sized>(f: &mut F, e: Expr) -> Expr {
+    match e {
+        Expr::Const(lit) => Expr::Const(f.fold_literal(lit)),
+        Expr::Let(name, ty, box e1, box e2) => {
+            let ty = f.fold_type(ty);
+            let e1 = f.fold_expr(e1);
+            Expr::Let(name, ty, box e1, box f.fold_expr(e2))
+        }
+        Expr::Var(name) => Expr::Var(name),
+        Expr::Load(box e) => Expr::Load(box f.fold_expr(e)),
+        Expr::Assign(box e1, box e2) => {
+            let e1 = f.fold_expr(e1);
+            Expr::Assign(box e1, box f.fold_expr(e2))
+        }
+        Expr::Call(box func, args) => {
+            let func = f.fold_expr(func);
+            Expr::Call(
+                box func,
+                args.into_iter().map(|arg| f.fold_expr(arg)).collect(),
+            )
+        }
+        Expr::If(box cond, box e1, box e2) => {
+            let cond = f.fold_expr(cond);
+            let e1 = f.fold_expr(e1);
+            Expr::If(box cond, box e1, box f.fold_expr(e2))
+        }
+        Expr::BinOp(op, box e1, box e2) => {
+            let e1 = f.fold_expr(e1);
+            Expr::BinOp(op, box e1, box f.fold_expr(e2))
+        }
+        Expr::ArrayAt(box arr, box idx) => {
+            let arr = f.fold_expr(arr);
+            Expr::ArrayAt(box arr, box f.fold_expr(idx))
+        }
+        Expr::TupleAt(box e, idx) => Expr::TupleAt(box f.fold_expr(e), idx),
+        Expr::PrintNum(box e) => Expr::PrintNum(box f.fold_expr(e)),
+    }
+}
+
+pub fn fold_literal<F: Folder + ?Sized>(f: &mut F, lit: Literal) -> Literal {
+    match lit {
+        Literal::Bool(_) | Literal::Char(_) | Literal::Int(_) => lit,
+        Literal::Array(elems, ty) => {
+            let elems = elems.into_iter().map(|e| f.fold_expr(e)).collect();
+            Literal::Array(elems, f.fold_type(ty))
+        }
+        Literal::Tuple(elems) => {
+            Literal::Tuple(elems.into_iter().map(|e| f.fold_expr(e)).collect())
+        }
+        Literal::ExternalFunc(name, ty, cc) => Literal::ExternalFunc(name, f.fold_type(ty), cc),
+    }
+}
+
+pub fn fold_type<F: Folder + ?Sized>(f: &mut F, ty: Type) -> Type {
+    match ty {
+        Type::Void | Type::Bool | Type::Char | Type::Int => ty,
+        Type::Func(params, box ret_ty, is_variadic) => {
+            let params = params.into_iter().map(|ty| f.fold_type(ty)).collect();
+            Type::Func(params, box f.fold_type(ret_ty), is_variadic)
+        }
+        Type::Array(box ty, len) => Type::Array(box f.fold_type(ty), len),
+        Type::Pointer(box ty) => Type::Pointer(box f.fold_type(ty)),
+        Type::Tuple(elems) => Type::Tuple(elems.into_iter().map(|ty| f.fold_type(ty)).collect()),
+    }
+}
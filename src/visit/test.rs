@@ -0,0 +1,103 @@
+use super::*;
+
+fn sample() -> Nf {
+    // int f({int, char} t) { return load (load t).0; }
+    // let a: int[2] = [1, 2] in f({3, 'c'})
+    Nf {
+        funcs: vec![Func {
+            name: Ident::new("f"),
+            params: vec![(Ident::new("t"), Type::Tuple(vec![Type::Int, Type::Char]))],
+            ret_type: Type::Int,
+            body: Expr::Load(box Expr::TupleAt(box Expr::Var(Ident::new("t")), 0)),
+            visibility: Visibility::Public,
+        }],
+        body: Some(Expr::Let(
+            Ident::new("a"),
+            Type::Array(box Type::Int, 2),
+            box Expr::Const(Literal::Array(
+                vec![Expr::Const(Literal::Int(1)), Expr::Const(Literal::Int(2))],
+                Type::Int,
+            )),
+            box Expr::Call(
+                box Expr::Var(Ident::new("f")),
+                vec![Expr::Const(Literal::Tuple(vec![
+                    Expr::Const(Literal::Int(3)),
+                    Expr::Const(Literal::Char('c')),
+                ]))],
+            ),
+        )),
+    }
+}
+
+#[test]
+fn visitor_test() {
+    struct Vars<'a>(Vec<&'a Ident>);
+    impl<'a> Visitor<'a> for Vars<'a> {
+        fn visit_expr(&mut self, e: &'a Expr) {
+            if let Expr::Var(ref name) = *e {
+                self.0.push(name);
+            }
+            walk_expr(self, e)
+        }
+    }
+    let nf = sample();
+    let mut vars = Vars(vec![]);
+    vars.visit_nf(&nf);
+    assert_eq!(vars.0, vec![&Ident::new("t"), &Ident::new("f")]);
+
+    struct Ints(usize);
+    impl<'a> Visitor<'a> for Ints {
+        fn visit_type(&mut self, ty: &'a Type) {
+            if ty == &Type::Int {
+                self.0 += 1;
+            }
+            walk_type(self, ty)
+        }
+    }
+    let mut ints = Ints(0);
+    ints.visit_nf(&nf);
+    // param, return type, type of `a`, and element type of the array literal
+    assert_eq!(ints.0, 4);
+}
+
+#[test]
+fn visitor_mut_test() {
+    struct CharToInt;
+    impl VisitorMut for CharToInt {
+        fn visit_type_mut(&mut self, ty: &mut Type) {
+            if ty == &Type::Char {
+                *ty = Type::Int;
+            }
+            walk_type_mut(self, ty)
+        }
+    }
+    let mut nf = sample();
+    CharToInt.visit_nf_mut(&mut nf);
+    assert_eq!(
+        nf.funcs[0].params[0].1,
+        Type::Tuple(vec![Type::Int, Type::Int])
+    );
+}
+
+#[test]
+fn folder_test() {
+    struct Double;
+    impl Folder for Double {
+        fn fold_literal(&mut self, lit: Literal) -> Literal {
+            match fold_literal(self, lit) {
+                Literal::Int(n) => Literal::Int(n * 2),
+                lit => lit,
+            }
+        }
+    }
+    let nf = Double.fold_nf(sample());
+    match nf.body {
+        Some(Expr::Let(_, _, box Expr::Const(Literal::Array(ref elems, _)), _)) => assert_eq!(
+            elems,
+            &vec![Expr::Const(Literal::Int(2)), Expr::Const(Literal::Int(4))]
+        ),
+        ref body => panic!("unexpected body: {:?}", body),
+    }
+    // nodes without literals are untouched
+    assert_eq!(nf.funcs, sample().funcs);
+}